@@ -0,0 +1,218 @@
+//! Derive macros for `nvpair`'s `NvEncode` and `NvDecode`
+//!
+//! Structs with named fields are mapped to an nvlist with one pair per field. By default the pair
+//! name is the field name, which may be overridden with `#[nv(rename = "...")]`.
+//!
+//! - `Option<T>` fields are omitted when `None` (on encode) and become `None` when missing (on
+//!   decode)
+//! - fields of a type that itself derives `NvEncode`/`NvDecode` are nested nvlists
+//! - `Vec<T>`, where `T` derives `NvEncode`/`NvDecode`, is an nvlist array
+//!
+//! ```ignore
+//! #[derive(NvEncode, NvDecode)]
+//! struct CreateProps {
+//!     compression: String,
+//!     #[nv(rename = "recordsize")]
+//!     record_size: u64,
+//! }
+//! ```
+#![warn(rust_2018_idioms)]
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::spanned::Spanned;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, Lit, Meta, NestedMeta};
+
+struct Field {
+    ident: syn::Ident,
+    nv_name: String,
+    optional: bool,
+}
+
+fn is_option(ty: &syn::Type) -> bool {
+    match ty {
+        syn::Type::Path(p) if p.qself.is_none() => {
+            matches!(p.path.segments.last(), Some(s) if s.ident == "Option")
+        }
+        _ => false,
+    }
+}
+
+fn nv_name(field: &syn::Field) -> syn::Result<String> {
+    let ident = field.ident.as_ref().unwrap();
+    let mut name = ident.to_string().trim_start_matches("r#").to_owned();
+
+    for attr in field.attrs.iter().filter(|a| a.path.is_ident("nv")) {
+        let list = match attr.parse_meta()? {
+            Meta::List(l) => l,
+            m => return Err(syn::Error::new(m.span(), "expected `#[nv(...)]`")),
+        };
+
+        for nested in list.nested {
+            match nested {
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("rename") => {
+                    match nv.lit {
+                        Lit::Str(s) => name = s.value(),
+                        l => return Err(syn::Error::new(l.span(), "expected a string literal")),
+                    }
+                }
+                n => return Err(syn::Error::new(n.span(), "unknown `nv` attribute")),
+            }
+        }
+    }
+
+    if name.contains('\0') {
+        return Err(syn::Error::new(
+            field.span(),
+            "nvpair names may not contain NUL",
+        ));
+    }
+
+    Ok(name)
+}
+
+fn fields(input: &DeriveInput) -> syn::Result<Vec<Field>> {
+    let named = match &input.data {
+        Data::Struct(s) => match &s.fields {
+            Fields::Named(n) => &n.named,
+            _ => {
+                return Err(syn::Error::new(
+                    input.ident.span(),
+                    "only structs with named fields are supported",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new(
+                input.ident.span(),
+                "only structs with named fields are supported",
+            ))
+        }
+    };
+
+    named
+        .iter()
+        .map(|f| {
+            Ok(Field {
+                ident: f.ident.clone().unwrap(),
+                nv_name: nv_name(f)?,
+                optional: is_option(&f.ty),
+            })
+        })
+        .collect()
+}
+
+/// Implements `NvListEncode` (one pair per field) and `NvEncode` (as a nested nvlist)
+#[proc_macro_derive(NvEncode, attributes(nv))]
+pub fn derive_nv_encode(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_encode(&input)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+fn expand_encode(input: &DeriveInput) -> syn::Result<TokenStream> {
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let inserts = fields(input)?.into_iter().map(|f| {
+        let Field {
+            ident,
+            nv_name,
+            optional,
+        } = f;
+        if optional {
+            quote! {
+                if let ::std::option::Option::Some(v) = &self.#ident {
+                    ::nvpair::NvEncode::insert_into(v, #nv_name, nv)?;
+                }
+            }
+        } else {
+            quote! {
+                ::nvpair::NvEncode::insert_into(&self.#ident, #nv_name, nv)?;
+            }
+        }
+    });
+
+    Ok(quote! {
+        impl #impl_generics ::nvpair::NvListEncode for #ident #ty_generics #where_clause {
+            fn encode_into(&self, nv: &mut ::nvpair::NvListRef) -> ::nvpair::NvResult<()> {
+                #(#inserts)*
+                ::std::result::Result::Ok(())
+            }
+        }
+
+        impl #impl_generics ::nvpair::NvEncode for #ident #ty_generics #where_clause {
+            fn insert_into<S: ::nvpair::CStrArgument>(
+                &self,
+                name: S,
+                nv: &mut ::nvpair::NvListRef,
+            ) -> ::nvpair::NvResult<()> {
+                let list = ::nvpair::NvListEncode::to_nvlist(self)?;
+                ::nvpair::NvEncode::insert_into(&*list, name, nv)
+            }
+        }
+    })
+}
+
+/// Implements `NvListDecode` (one pair per field) and `NvDecode` (from a nested nvlist)
+#[proc_macro_derive(NvDecode, attributes(nv))]
+pub fn derive_nv_decode(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_decode(&input)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+fn expand_decode(input: &DeriveInput) -> syn::Result<TokenStream> {
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let lookups = fields(input)?.into_iter().map(|f| {
+        let Field {
+            ident,
+            nv_name,
+            optional,
+        } = f;
+        // `NvListRef::find()` also works on lists without `NV_UNIQUE_NAME`
+        if optional {
+            quote! {
+                #ident: match nv.find(#nv_name).ok() {
+                    ::std::option::Option::Some(p) => {
+                        ::std::option::Option::Some(::nvpair::NvDecode::decode(p)?)
+                    }
+                    ::std::option::Option::None => ::std::option::Option::None,
+                },
+            }
+        } else {
+            quote! {
+                #ident: ::nvpair::NvDecode::decode(nv.find(#nv_name)?)?,
+            }
+        }
+    });
+
+    Ok(quote! {
+        impl #impl_generics ::nvpair::NvListDecode for #ident #ty_generics #where_clause {
+            fn decode_from(nv: &::nvpair::NvListRef) -> ::nvpair::NvResult<Self> {
+                ::std::result::Result::Ok(Self {
+                    #(#lookups)*
+                })
+            }
+        }
+
+        impl #impl_generics ::nvpair::NvDecode for #ident #ty_generics #where_clause {
+            fn decode(pair: &::nvpair::NvPair) -> ::nvpair::NvResult<Self> {
+                match pair.as_list() {
+                    ::std::option::Option::Some(l) => ::nvpair::NvListDecode::decode_from(l),
+                    ::std::option::Option::None => {
+                        ::std::result::Result::Err(::nvpair::NvError::TypeMismatch {
+                            name: pair.name().to_owned(),
+                            expected: "nvlist",
+                            found: pair.data_type().name(),
+                        })
+                    }
+                }
+            }
+        }
+    })
+}
@@ -0,0 +1,123 @@
+use nvpair::{NvDecode, NvEncode, NvListDecode, NvListEncode};
+
+#[derive(Debug, PartialEq, NvEncode, NvDecode)]
+struct CreateProps {
+    compression: String,
+    #[nv(rename = "recordsize")]
+    record_size: u64,
+    quota: Option<u64>,
+}
+
+#[derive(Debug, PartialEq, NvEncode, NvDecode)]
+struct Child {
+    path: String,
+    guid: u64,
+}
+
+#[derive(Debug, PartialEq, NvEncode, NvDecode)]
+struct Tree {
+    #[nv(rename = "type")]
+    kind: String,
+    props: CreateProps,
+    children: Vec<Child>,
+}
+
+#[test]
+fn encode_rename() {
+    let p = CreateProps {
+        compression: "lz4".to_owned(),
+        record_size: 128 * 1024,
+        quota: None,
+    };
+
+    let nv = p.to_nvlist().unwrap();
+    assert_eq!(
+        nv.lookup_string("compression").unwrap().to_str().unwrap(),
+        "lz4"
+    );
+    assert_eq!(nv.lookup_uint64("recordsize").unwrap(), 128 * 1024);
+    assert!(!nv.exists("record_size"));
+    assert!(!nv.exists("quota"));
+}
+
+#[test]
+fn round_trip() {
+    let t = Tree {
+        kind: "root".to_owned(),
+        props: CreateProps {
+            compression: "zstd".to_owned(),
+            record_size: 4096,
+            quota: Some(1 << 30),
+        },
+        children: vec![
+            Child {
+                path: "/dev/sda".to_owned(),
+                guid: 1,
+            },
+            Child {
+                path: "/dev/sdb".to_owned(),
+                guid: 2,
+            },
+        ],
+    };
+
+    let nv = t.to_nvlist().unwrap();
+    match nv.lookup("children").unwrap().data() {
+        nvpair::NvData::NvListRefArray(v) => assert_eq!(v.len(), 2),
+        d => panic!("unexpected data for children: {:?}", d),
+    }
+
+    let t2 = Tree::decode_from(&nv).unwrap();
+    assert_eq!(t, t2);
+}
+
+#[test]
+fn decode_non_unique() {
+    let mut nv = nvpair::NvList::new();
+    nv.insert("compression", "off").unwrap();
+    nv.insert("recordsize", &4096u64).unwrap();
+    nv.insert("quota", &1u64).unwrap();
+    nv.insert("quota", &2u64).unwrap();
+    assert_eq!(nv.find("quota").unwrap().as_u64(), Some(1));
+
+    let p = CreateProps::decode_from(&nv).unwrap();
+    assert_eq!(
+        p,
+        CreateProps {
+            compression: "off".to_owned(),
+            record_size: 4096,
+            quota: Some(1),
+        }
+    );
+}
+
+#[test]
+fn decode_missing_field() {
+    let mut nv = nvpair::NvList::new_unique_names();
+    nv.insert("compression", "off").unwrap();
+
+    match CreateProps::decode_from(&nv).unwrap_err() {
+        nvpair::NvError::NotFound { name } => assert_eq!(name.to_str().unwrap(), "recordsize"),
+        e => panic!("unexpected error: {}", e),
+    }
+}
+
+#[test]
+fn decode_wrong_type() {
+    let mut nv = nvpair::NvList::new_unique_names();
+    nv.insert("compression", "off").unwrap();
+    nv.insert("recordsize", "big").unwrap();
+
+    match CreateProps::decode_from(&nv).unwrap_err() {
+        nvpair::NvError::TypeMismatch {
+            name,
+            expected,
+            found,
+        } => {
+            assert_eq!(name.to_str().unwrap(), "recordsize");
+            assert_eq!(expected, "uint64");
+            assert_eq!(found, "string");
+        }
+        e => panic!("unexpected error: {}", e),
+    }
+}
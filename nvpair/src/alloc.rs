@@ -0,0 +1,301 @@
+//! Creating nvlists with specific flags or a custom allocator
+//!
+//! libnvpair allocates every nvlist (and all of its pairs) through an `nv_alloc_t`. Normally
+//! that is `nv_alloc_sleep`, which uses `malloc()`. [`NvAllocator`] wraps any [`NvAlloc`]
+//! implementation in an `nv_alloc_t`, and [`NvListBuilder::build_in()`] creates lists that use
+//! it. As the allocator must outlive those lists, they are returned as an [`NvListIn`], which
+//! borrows the allocator.
+//!
+//! [`NvAllocator::arena()`] provides a bump allocator where freeing is a no-op: memory is only
+//! returned when the allocator is dropped or [`reset`](NvAllocator::reset). This suits building
+//! many short lived lists (for example, one hold list per snapshot) without a trip through
+//! `malloc()`/`free()` for every pair.
+use crate::{sys, NvError, NvList, NvListRef, NvResult};
+use foreign_types::ForeignType;
+use std::alloc::Layout;
+use std::marker::PhantomData;
+use std::os::raw::{c_uint, c_void};
+use std::sync::Mutex;
+use std::{fmt, ops, panic, ptr};
+
+/// A source of memory for nvlists
+///
+/// # Safety
+///
+/// `alloc()` must return either null or a pointer to at least `size` bytes, aligned to 8 bytes,
+/// which stays valid until it is passed to `free()` or the allocator is dropped or reset.
+pub unsafe trait NvAlloc: Send + Sync {
+    /// Allocate `size` bytes, returning null on failure
+    fn alloc(&self, size: usize) -> *mut u8;
+
+    /// Release memory returned by `alloc()`. `size` is the size that was requested.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must have been returned by `alloc()` on this allocator with the same `size`, and
+    /// must not be used afterwards.
+    unsafe fn free(&self, ptr: *mut u8, size: usize);
+
+    /// Release everything allocated so far. Only called when no lists using this allocator
+    /// remain.
+    fn reset(&mut self) {}
+}
+
+struct Inner {
+    nva: sys::nv_alloc_t,
+    alloc: Box<dyn NvAlloc>,
+}
+
+static OPS: sys::nv_alloc_ops_t = sys::nv_alloc_ops_t {
+    nv_ao_init: None,
+    nv_ao_fini: None,
+    nv_ao_alloc: Some(nva_alloc),
+    nv_ao_free: Some(nva_free),
+    nv_ao_reset: None,
+};
+
+unsafe extern "C" fn nva_alloc(nva: *mut sys::nv_alloc_t, size: sys::size_t) -> *mut c_void {
+    let inner = &*((*nva).nva_arg as *const Inner);
+    // unwinding into C is undefined. Report a panic as an allocation failure instead.
+    panic::catch_unwind(panic::AssertUnwindSafe(|| inner.alloc.alloc(size as usize)))
+        .unwrap_or(ptr::null_mut()) as *mut c_void
+}
+
+unsafe extern "C" fn nva_free(nva: *mut sys::nv_alloc_t, buf: *mut c_void, size: sys::size_t) {
+    let inner = &*((*nva).nva_arg as *const Inner);
+    let _ = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+        inner.alloc.free(buf as *mut u8, size as usize)
+    }));
+}
+
+/// An `nv_alloc_t` backed by an [`NvAlloc`]
+pub struct NvAllocator {
+    inner: Box<Inner>,
+}
+
+// `nva` is only read by libnvpair, and `NvAlloc` requires `Send + Sync`
+unsafe impl Send for NvAllocator {}
+unsafe impl Sync for NvAllocator {}
+
+impl NvAllocator {
+    pub fn new<A: NvAlloc + 'static>(alloc: A) -> Self {
+        let mut inner = Box::new(Inner {
+            nva: sys::nv_alloc_t {
+                nva_ops: &OPS,
+                nva_arg: ptr::null_mut(),
+            },
+            alloc: Box::new(alloc),
+        });
+        inner.nva.nva_arg = &mut *inner as *mut Inner as *mut c_void;
+        NvAllocator { inner }
+    }
+
+    /// A bump allocator which only releases memory when it is dropped or reset
+    pub fn arena() -> Self {
+        Self::new(Arena::default())
+    }
+
+    /// Release all memory held by the allocator (for allocators that support it, like
+    /// [`arena()`](Self::arena))
+    ///
+    /// Taking `&mut self` ensures no lists allocated from it are still around.
+    pub fn reset(&mut self) {
+        self.inner.alloc.reset()
+    }
+
+    pub fn as_ptr(&self) -> *const sys::nv_alloc_t {
+        &self.inner.nva
+    }
+}
+
+impl fmt::Debug for NvAllocator {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("NvAllocator")
+            .field("nva", &self.as_ptr())
+            .finish()
+    }
+}
+
+const ARENA_ALIGN: usize = 16;
+const ARENA_CHUNK: usize = 64 * 1024;
+
+#[derive(Default)]
+struct ArenaState {
+    chunks: Vec<(*mut u8, Layout)>,
+    pos: usize,
+    end: usize,
+}
+
+#[derive(Default)]
+struct Arena {
+    state: Mutex<ArenaState>,
+}
+
+// the raw chunk pointers are only touched with the lock held
+unsafe impl Send for Arena {}
+unsafe impl Sync for Arena {}
+
+impl ArenaState {
+    fn new_chunk(&mut self, size: usize) -> *mut u8 {
+        let layout = match Layout::from_size_align(size, ARENA_ALIGN) {
+            Ok(l) => l,
+            Err(_) => return ptr::null_mut(),
+        };
+        let p = unsafe { std::alloc::alloc(layout) };
+        if !p.is_null() {
+            self.chunks.push((p, layout));
+        }
+        p
+    }
+
+    fn release(&mut self) {
+        for (p, layout) in self.chunks.drain(..) {
+            unsafe { std::alloc::dealloc(p, layout) };
+        }
+        self.pos = 0;
+        self.end = 0;
+    }
+}
+
+unsafe impl NvAlloc for Arena {
+    fn alloc(&self, size: usize) -> *mut u8 {
+        let size = match size.checked_add(ARENA_ALIGN - 1) {
+            Some(s) => s & !(ARENA_ALIGN - 1),
+            None => return ptr::null_mut(),
+        };
+        let mut st = self.state.lock().unwrap();
+
+        // big allocations get their own chunk, so they don't waste the rest of the current one
+        if size > ARENA_CHUNK / 4 {
+            return st.new_chunk(size);
+        }
+
+        if st.end - st.pos < size {
+            let p = st.new_chunk(ARENA_CHUNK);
+            if p.is_null() {
+                return p;
+            }
+            st.pos = p as usize;
+            st.end = st.pos + ARENA_CHUNK;
+        }
+
+        let p = st.pos;
+        st.pos += size;
+        p as *mut u8
+    }
+
+    unsafe fn free(&self, _ptr: *mut u8, _size: usize) {}
+
+    fn reset(&mut self) {
+        self.state.get_mut().unwrap().release()
+    }
+}
+
+impl Drop for Arena {
+    fn drop(&mut self) {
+        self.reset()
+    }
+}
+
+/// Create an `NvList` with chosen flags, and optionally a custom allocator
+///
+/// By default the list has no uniqueness constraint, like [`NvList::try_new()`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NvListBuilder {
+    flags: c_uint,
+}
+
+impl NvListBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `NV_UNIQUE_NAME`: adding a pair replaces any existing pair with the same name
+    ///
+    /// Takes precedence over [`unique_name_type()`](Self::unique_name_type).
+    pub fn unique_names(mut self, unique: bool) -> Self {
+        self.set(sys::NV_UNIQUE_NAME, unique);
+        self
+    }
+
+    /// `NV_UNIQUE_NAME_TYPE`: adding a pair replaces any existing pair with the same name and
+    /// data type
+    pub fn unique_name_type(mut self, unique: bool) -> Self {
+        self.set(sys::NV_UNIQUE_NAME_TYPE, unique);
+        self
+    }
+
+    fn set(&mut self, flag: c_uint, on: bool) {
+        if on {
+            self.flags |= flag;
+        } else {
+            self.flags &= !flag;
+        }
+    }
+
+    /// Allocate the list with `nvlist_alloc()`
+    ///
+    /// Returns an error if memory allocation fails
+    #[doc(alias = "nvlist_alloc")]
+    pub fn build(&self) -> NvResult<NvList> {
+        let mut n = ptr::null_mut();
+        let v = unsafe { sys::nvlist_alloc(&mut n, self.flags, 0) };
+        if v != 0 {
+            Err(NvError::from_errno(v))
+        } else {
+            Ok(unsafe { NvList::from_ptr(n) })
+        }
+    }
+
+    /// Allocate the list (and everything later added to it) from `alloc`
+    ///
+    /// Returns an error if memory allocation fails
+    #[doc(alias = "nvlist_xalloc")]
+    pub fn build_in<'a>(&self, alloc: &'a NvAllocator) -> NvResult<NvListIn<'a>> {
+        let mut n = ptr::null_mut();
+        let v = unsafe { sys::nvlist_xalloc(&mut n, self.flags, alloc.as_ptr() as *mut _) };
+        if v != 0 {
+            Err(NvError::from_errno(v))
+        } else {
+            Ok(NvListIn {
+                nv: unsafe { NvList::from_ptr(n) },
+                alloc: PhantomData,
+            })
+        }
+    }
+}
+
+/// An nvlist allocated from an [`NvAllocator`], which it may not outlive
+///
+/// Dereferences to [`NvListRef`]. Lists inserted into it are copied into the same allocator,
+/// while copies made with [`NvListRef::try_to_owned()`] use the default allocator.
+pub struct NvListIn<'a> {
+    nv: NvList,
+    alloc: PhantomData<&'a NvAllocator>,
+}
+
+impl<'a> ops::Deref for NvListIn<'a> {
+    type Target = NvListRef;
+
+    fn deref(&self) -> &NvListRef {
+        &self.nv
+    }
+}
+
+impl<'a> ops::DerefMut for NvListIn<'a> {
+    fn deref_mut(&mut self) -> &mut NvListRef {
+        &mut self.nv
+    }
+}
+
+impl<'a> AsRef<NvListRef> for NvListIn<'a> {
+    fn as_ref(&self) -> &NvListRef {
+        self
+    }
+}
+
+impl<'a> fmt::Debug for NvListIn<'a> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_tuple("NvListIn").field(&self.nv).finish()
+    }
+}
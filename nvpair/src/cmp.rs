@@ -0,0 +1,175 @@
+//! Structural comparison of nvlists
+use crate::{sys, NvData, NvList, NvListRef, NvPair};
+use std::collections::HashMap;
+
+impl NvListRef {
+    fn has_unique_names(&self) -> bool {
+        unsafe { sys::nvlist_nvflag(self.as_ptr() as *mut _) & sys::NV_UNIQUE_NAME != 0 }
+    }
+}
+
+/// Lists are equal if they contain equal pairs
+///
+/// When both lists have the `NV_UNIQUE_NAME` constraint, the order of their pairs is ignored.
+/// Otherwise (as a name may appear more than once) the pairs must also be in the same order.
+impl PartialEq for NvListRef {
+    fn eq(&self, other: &NvListRef) -> bool {
+        if self.has_unique_names() && other.has_unique_names() {
+            // match names exactly: `nvlist_lookup_nvpair()` would treat `[` in a name as an
+            // array index
+            let theirs: HashMap<&[u8], &NvPair> =
+                other.iter().map(|p| (p.name().to_bytes(), p)).collect();
+            self.iter().count() == theirs.len()
+                && self
+                    .iter()
+                    .all(|pair| match theirs.get(pair.name().to_bytes()) {
+                        Some(o) => pair.data() == o.data(),
+                        None => false,
+                    })
+        } else {
+            let mut a = self.iter();
+            let mut b = other.iter();
+            loop {
+                match (a.next(), b.next()) {
+                    (None, None) => return true,
+                    (Some(x), Some(y)) if x.name() == y.name() && x.data() == y.data() => {}
+                    _ => return false,
+                }
+            }
+        }
+    }
+}
+
+impl PartialEq for NvList {
+    fn eq(&self, other: &NvList) -> bool {
+        **self == **other
+    }
+}
+
+/// Data is equal if it has the same type and value. `Unknown` data isn't equal to anything.
+impl<'a, 'b> PartialEq<NvData<'b>> for NvData<'a> {
+    fn eq(&self, other: &NvData<'b>) -> bool {
+        match (self, other) {
+            (NvData::Bool, NvData::Bool) => true,
+            (NvData::BoolV(a), NvData::BoolV(b)) => a == b,
+            (NvData::Byte(a), NvData::Byte(b)) => a == b,
+            (NvData::Int8(a), NvData::Int8(b)) => a == b,
+            (NvData::Uint8(a), NvData::Uint8(b)) => a == b,
+            (NvData::Int16(a), NvData::Int16(b)) => a == b,
+            (NvData::Uint16(a), NvData::Uint16(b)) => a == b,
+            (NvData::Int32(a), NvData::Int32(b)) => a == b,
+            (NvData::Uint32(a), NvData::Uint32(b)) => a == b,
+            (NvData::Int64(a), NvData::Int64(b)) => a == b,
+            (NvData::Uint64(a), NvData::Uint64(b)) => a == b,
+            (NvData::Str(a), NvData::Str(b)) => a == b,
+            (NvData::NvListRef(a), NvData::NvListRef(b)) => a == b,
+            (NvData::ByteArray(a), NvData::ByteArray(b)) => a == b,
+            (NvData::Int8Array(a), NvData::Int8Array(b)) => a == b,
+            (NvData::Uint8Array(a), NvData::Uint8Array(b)) => a == b,
+            (NvData::Int16Array(a), NvData::Int16Array(b)) => a == b,
+            (NvData::Uint16Array(a), NvData::Uint16Array(b)) => a == b,
+            (NvData::Int32Array(a), NvData::Int32Array(b)) => a == b,
+            (NvData::Uint32Array(a), NvData::Uint32Array(b)) => a == b,
+            (NvData::Int64Array(a), NvData::Int64Array(b)) => a == b,
+            (NvData::Uint64Array(a), NvData::Uint64Array(b)) => a == b,
+            (NvData::NvListRefArray(a), NvData::NvListRefArray(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+/// The paths which differ between two nvlists, as found by [`diff()`]
+///
+/// Paths use the same form as [`NvListRef::lookup_path()`]: pair names separated by `/`, with
+/// elements of nvlist arrays named by their index.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NvDiff {
+    /// Paths only present in the second list
+    pub added: Vec<String>,
+    /// Paths only present in the first list
+    pub removed: Vec<String>,
+    /// Paths present in both lists, with different values or types
+    pub changed: Vec<String>,
+}
+
+impl NvDiff {
+    /// True if the lists were equal
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Compare `a` with `b`, recursing into nested nvlists and nvlist arrays
+///
+/// Pairs are matched by name regardless of their order. If a name appears more than once in a
+/// list, the first occurrence in `a` is matched with the first in `b`, the second with the
+/// second, and so on. Elements of nvlist arrays are compared by index.
+///
+/// ```no_run
+/// # let (old, new) = (nvpair::NvList::new(), nvpair::NvList::new());
+/// let d = nvpair::diff(&old, &new);
+/// for path in &d.changed {
+///     println!("{} changed to {:?}", path, new.lookup_path(path));
+/// }
+/// ```
+pub fn diff(a: &NvListRef, b: &NvListRef) -> NvDiff {
+    let mut d = NvDiff::default();
+    diff_lists(&mut d, "", a, b);
+    d
+}
+
+fn join(prefix: &str, name: &str) -> String {
+    if prefix.is_empty() {
+        name.to_owned()
+    } else {
+        format!("{}/{}", prefix, name)
+    }
+}
+
+fn diff_lists(d: &mut NvDiff, prefix: &str, a: &NvListRef, b: &NvListRef) {
+    let mut in_b: HashMap<&[u8], Vec<&NvPair>> = HashMap::new();
+    for pair in b {
+        in_b.entry(pair.name().to_bytes()).or_default().push(pair);
+    }
+    let mut seen: HashMap<&[u8], usize> = HashMap::new();
+
+    for pair in a {
+        let name = pair.name().to_bytes();
+        let n = seen.entry(name).or_insert(0);
+        let path = join(prefix, &pair.name().to_string_lossy());
+        match in_b.get(name).and_then(|v| v.get(*n)) {
+            Some(other) => diff_data(d, path, pair.data(), other.data()),
+            None => d.removed.push(path),
+        }
+        *n += 1;
+    }
+
+    // pairs of `b` beyond the number with the same name in `a`, in the order they appear in `b`
+    for pair in b {
+        match seen.get_mut(pair.name().to_bytes()) {
+            Some(n) if *n > 0 => *n -= 1,
+            _ => d.added.push(join(prefix, &pair.name().to_string_lossy())),
+        }
+    }
+}
+
+fn diff_data(d: &mut NvDiff, path: String, a: NvData<'_>, b: NvData<'_>) {
+    match (a, b) {
+        (NvData::NvListRef(a), NvData::NvListRef(b)) => diff_lists(d, &path, a, b),
+        (NvData::NvListRefArray(a), NvData::NvListRefArray(b)) => {
+            for i in 0..a.len().max(b.len()) {
+                let p = join(&path, &i.to_string());
+                match (a.get(i), b.get(i)) {
+                    (Some(x), Some(y)) => diff_lists(d, &p, x, y),
+                    (Some(_), None) => d.removed.push(p),
+                    (None, _) => d.added.push(p),
+                }
+            }
+        }
+        (a, b) => {
+            if a != b {
+                d.changed.push(path)
+            }
+        }
+    }
+}
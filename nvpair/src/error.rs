@@ -0,0 +1,145 @@
+use crate::NvListRef;
+use cstr_argument::CStrArgument;
+use std::os::raw::c_int;
+use std::{error, ffi, fmt, io, ptr};
+
+// the same on every platform libnvpair supports
+const ENOENT: c_int = 2;
+const ENOMEM: c_int = 12;
+
+/// Errors from operations on nvlists
+#[derive(Debug)]
+pub enum NvError {
+    /// There is no pair named `name`
+    NotFound { name: ffi::CString },
+    /// The pair named `name` holds a `found`, not the `expected` type
+    ///
+    /// Types are named as in [`NvType::name()`](crate::NvType::name).
+    TypeMismatch {
+        name: ffi::CString,
+        expected: &'static str,
+        found: &'static str,
+    },
+    /// A name or string value contains a NUL byte, which nvlists can't represent
+    InteriorNul,
+    /// libnvpair failed to allocate memory
+    NoMemory,
+    /// A packed nvlist is malformed. `offset` is the position of the problem within the packed
+    /// data, when known.
+    Corrupt { offset: Option<usize> },
+    /// Any other error: other `errno` values returned by libnvpair, invalid arguments, or errors
+    /// from the reader or writer used with [`NvList::read_from()`](crate::NvList::read_from) and
+    /// [`NvListRef::pack_into()`](crate::NvListRef::pack_into)
+    Io(io::Error),
+}
+
+pub type NvResult<T> = Result<T, NvError>;
+
+impl NvError {
+    /// Convert an error number returned by libnvpair
+    pub fn from_errno(v: c_int) -> Self {
+        match v {
+            ENOMEM => NvError::NoMemory,
+            _ => NvError::Io(io::Error::from_raw_os_error(v)),
+        }
+    }
+
+    pub(crate) fn not_found<S: AsRef<[u8]>>(name: S) -> Self {
+        NvError::NotFound {
+            name: ffi::CString::new(name.as_ref()).unwrap_or_default(),
+        }
+    }
+
+    pub(crate) fn invalid_input<E: Into<Box<dyn error::Error + Send + Sync>>>(e: E) -> Self {
+        NvError::Io(io::Error::new(io::ErrorKind::InvalidInput, e))
+    }
+
+    /// Convert the error returned by one of the `nvlist_lookup_*()` functions
+    ///
+    /// libnvpair returns `ENOENT` both when there is no pair named `name`, and when the pair
+    /// has another type, so check which it is.
+    pub(crate) fn lookup(
+        nv: &NvListRef,
+        name: &ffi::CStr,
+        v: c_int,
+        expected: &'static str,
+    ) -> Self {
+        if v != ENOENT {
+            return NvError::from_errno(v);
+        }
+
+        let mut np = ptr::null_mut();
+        let v = unsafe {
+            nvpair_sys::nvlist_lookup_nvpair(nv.as_ptr() as *mut _, name.as_ptr(), &mut np)
+        };
+        if v != 0 {
+            NvError::NotFound {
+                name: name.to_owned(),
+            }
+        } else {
+            let pair = unsafe { <crate::NvPair as foreign_types::ForeignTypeRef>::from_ptr(np) };
+            NvError::TypeMismatch {
+                name: name.to_owned(),
+                expected,
+                found: pair.data_type().name(),
+            }
+        }
+    }
+}
+
+/// Convert a name or string argument, reporting interior NULs
+pub(crate) fn cstr<S: CStrArgument>(s: S) -> NvResult<S::Output> {
+    s.try_into_cstr().map_err(|_| NvError::InteriorNul)
+}
+
+impl fmt::Display for NvError {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NvError::NotFound { name } => write!(fmt, "no nvpair named {:?}", name),
+            NvError::TypeMismatch {
+                name,
+                expected,
+                found,
+            } => write!(
+                fmt,
+                "nvpair {:?} is a {}, expected a {}",
+                name, found, expected
+            ),
+            NvError::InteriorNul => write!(fmt, "string contains a NUL byte"),
+            NvError::NoMemory => write!(fmt, "out of memory"),
+            NvError::Corrupt { offset: Some(o) } => {
+                write!(fmt, "packed nvlist is corrupt at offset {}", o)
+            }
+            NvError::Corrupt { offset: None } => write!(fmt, "packed nvlist is corrupt"),
+            NvError::Io(e) => e.fmt(fmt),
+        }
+    }
+}
+
+impl error::Error for NvError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            NvError::Io(e) => e.source(),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for NvError {
+    fn from(e: io::Error) -> Self {
+        NvError::Io(e)
+    }
+}
+
+impl From<NvError> for io::Error {
+    fn from(e: NvError) -> Self {
+        let kind = match e {
+            NvError::NotFound { .. } => io::ErrorKind::NotFound,
+            NvError::TypeMismatch { .. } | NvError::Corrupt { .. } => io::ErrorKind::InvalidData,
+            NvError::InteriorNul => io::ErrorKind::InvalidInput,
+            NvError::NoMemory => return io::Error::from_raw_os_error(ENOMEM),
+            NvError::Io(e) => return e,
+        };
+        io::Error::new(kind, e)
+    }
+}
@@ -0,0 +1,239 @@
+//! Conversion between nvlists and JSON (`serde_json::Value`)
+//!
+//! JSON can't distinguish the many integer widths nvpairs use, or byte arrays from other arrays,
+//! so every value is tagged with its nvpair data type. An nvlist becomes a JSON object with one
+//! member per pair, whose value is an object with a single member named by the data type:
+//!
+//! ```json
+//! {
+//!   "compression": { "string": "lz4" },
+//!   "recordsize": { "uint64": 131072 },
+//!   "snapshots": { "boolean": null },
+//!   "guids": { "uint64_array": [1, 2] },
+//!   "payload": { "byte_array": "deadbeef" },
+//!   "vdev_tree": { "nvlist": { "type": { "string": "root" } } },
+//!   "children": { "nvlist_array": [ { "path": { "string": "/dev/sda" } } ] }
+//! }
+//! ```
+//!
+//! | tag                | JSON value                          |
+//! |--------------------|-------------------------------------|
+//! | `boolean`          | `null` (the pair has no value)      |
+//! | `boolean_value`    | `true` or `false`                   |
+//! | `byte`, `int8` ... `uint64` | number                     |
+//! | `string`           | string                              |
+//! | `nvlist`           | object (nvlist, as above)           |
+//! | `byte_array`       | string of lowercase hex digits      |
+//! | `int8_array` ... `uint64_array` | array of numbers       |
+//! | `nvlist_array`     | array of objects (nvlists)          |
+//!
+//! 64-bit integers are kept exact by `serde_json`, but consumers that parse numbers as doubles
+//! (javascript, for example) will lose precision above 2^53.
+//!
+//! The conversion is lossless for nvlists with unique names and UTF-8 strings. Lists which
+//! contain the same name more than once, or strings which are not UTF-8, are rejected.
+use crate::value::pairs_of;
+use crate::{NvError, NvList, NvListRef, NvResult, NvValue};
+use serde_json::{Map, Value};
+use std::convert::TryFrom;
+use std::ffi;
+
+fn invalid<E: Into<Box<dyn std::error::Error + Send + Sync>>>(e: E) -> NvError {
+    NvError::invalid_input(e)
+}
+
+fn hex_encode(v: &[u8]) -> String {
+    const DIGITS: &[u8; 16] = b"0123456789abcdef";
+    let mut s = String::with_capacity(v.len() * 2);
+    for b in v {
+        s.push(DIGITS[(b >> 4) as usize] as char);
+        s.push(DIGITS[(b & 0xf) as usize] as char);
+    }
+    s
+}
+
+fn hex_decode(s: &str) -> NvResult<Vec<u8>> {
+    fn digit(c: u8) -> NvResult<u8> {
+        match c {
+            b'0'..=b'9' => Ok(c - b'0'),
+            b'a'..=b'f' => Ok(c - b'a' + 10),
+            b'A'..=b'F' => Ok(c - b'A' + 10),
+            _ => Err(invalid(format!("invalid hex digit {:?}", c as char))),
+        }
+    }
+
+    let s = s.as_bytes();
+    if s.len() & 1 != 0 {
+        return Err(invalid("hex string has an odd number of digits"));
+    }
+    s.chunks(2)
+        .map(|c| Ok(digit(c[0])? << 4 | digit(c[1])?))
+        .collect()
+}
+
+fn pairs_to_json(pairs: &[(ffi::CString, NvValue)]) -> NvResult<Value> {
+    let mut m = Map::new();
+    for (name, value) in pairs {
+        let name = name.to_str().map_err(invalid)?;
+        if m.insert(name.to_owned(), value.to_json()?).is_some() {
+            return Err(invalid(format!(
+                "duplicate name {:?} can't be represented in JSON",
+                name
+            )));
+        }
+    }
+    Ok(Value::Object(m))
+}
+
+fn pairs_from_json(v: &Value) -> NvResult<Vec<(ffi::CString, NvValue)>> {
+    let m = v
+        .as_object()
+        .ok_or_else(|| invalid("expected a JSON object for an nvlist"))?;
+    m.iter()
+        .map(|(name, value)| {
+            Ok((
+                ffi::CString::new(name.as_str()).map_err(|_| NvError::InteriorNul)?,
+                NvValue::from_json(value)?,
+            ))
+        })
+        .collect()
+}
+
+fn num<T: TryFrom<i64> + TryFrom<u64>>(v: &Value) -> NvResult<T> {
+    let n = if let Some(n) = v.as_u64() {
+        T::try_from(n).ok()
+    } else if let Some(n) = v.as_i64() {
+        T::try_from(n).ok()
+    } else {
+        None
+    };
+    n.ok_or_else(|| invalid(format!("{} is not an integer in range", v)))
+}
+
+fn num_array<T: TryFrom<i64> + TryFrom<u64>>(v: &Value) -> NvResult<Vec<T>> {
+    v.as_array()
+        .ok_or_else(|| invalid(format!("{} is not an array", v)))?
+        .iter()
+        .map(num)
+        .collect()
+}
+
+impl NvValue {
+    /// Convert to the tagged JSON representation described in the [`json`](crate::json) module
+    pub fn to_json(&self) -> NvResult<Value> {
+        let (tag, v) = match self {
+            NvValue::Bool => ("boolean", Value::Null),
+            NvValue::BoolV(v) => ("boolean_value", Value::from(*v)),
+            NvValue::Byte(v) => ("byte", Value::from(*v)),
+            NvValue::Int8(v) => ("int8", Value::from(*v)),
+            NvValue::Uint8(v) => ("uint8", Value::from(*v)),
+            NvValue::Int16(v) => ("int16", Value::from(*v)),
+            NvValue::Uint16(v) => ("uint16", Value::from(*v)),
+            NvValue::Int32(v) => ("int32", Value::from(*v)),
+            NvValue::Uint32(v) => ("uint32", Value::from(*v)),
+            NvValue::Int64(v) => ("int64", Value::from(*v)),
+            NvValue::Uint64(v) => ("uint64", Value::from(*v)),
+            NvValue::Str(v) => ("string", Value::from(v.to_str().map_err(invalid)?)),
+            NvValue::NvList(v) => ("nvlist", pairs_to_json(v)?),
+            NvValue::ByteArray(v) => ("byte_array", Value::from(hex_encode(v))),
+            NvValue::Int8Array(v) => ("int8_array", Value::from(v.clone())),
+            NvValue::Uint8Array(v) => ("uint8_array", Value::from(v.clone())),
+            NvValue::Int16Array(v) => ("int16_array", Value::from(v.clone())),
+            NvValue::Uint16Array(v) => ("uint16_array", Value::from(v.clone())),
+            NvValue::Int32Array(v) => ("int32_array", Value::from(v.clone())),
+            NvValue::Uint32Array(v) => ("uint32_array", Value::from(v.clone())),
+            NvValue::Int64Array(v) => ("int64_array", Value::from(v.clone())),
+            NvValue::Uint64Array(v) => ("uint64_array", Value::from(v.clone())),
+            NvValue::NvListArray(v) => (
+                "nvlist_array",
+                Value::Array(
+                    v.iter()
+                        .map(|l| pairs_to_json(l))
+                        .collect::<NvResult<_>>()?,
+                ),
+            ),
+        };
+
+        let mut m = Map::new();
+        m.insert(tag.to_owned(), v);
+        Ok(Value::Object(m))
+    }
+
+    /// Parse the tagged JSON representation described in the [`json`](crate::json) module
+    pub fn from_json(v: &Value) -> NvResult<NvValue> {
+        let (tag, v) = match v.as_object() {
+            Some(m) if m.len() == 1 => m.iter().next().unwrap(),
+            _ => {
+                return Err(invalid(format!(
+                    "expected an object with a single type tag, got {}",
+                    v
+                )))
+            }
+        };
+
+        Ok(match tag.as_str() {
+            "boolean" if v.is_null() => NvValue::Bool,
+            "boolean_value" => NvValue::BoolV(
+                v.as_bool()
+                    .ok_or_else(|| invalid(format!("{} is not a bool", v)))?,
+            ),
+            "byte" => NvValue::Byte(num(v)?),
+            "int8" => NvValue::Int8(num(v)?),
+            "uint8" => NvValue::Uint8(num(v)?),
+            "int16" => NvValue::Int16(num(v)?),
+            "uint16" => NvValue::Uint16(num(v)?),
+            "int32" => NvValue::Int32(num(v)?),
+            "uint32" => NvValue::Uint32(num(v)?),
+            "int64" => NvValue::Int64(num(v)?),
+            "uint64" => NvValue::Uint64(num(v)?),
+            "string" => {
+                let s = v
+                    .as_str()
+                    .ok_or_else(|| invalid(format!("{} is not a string", v)))?;
+                NvValue::Str(ffi::CString::new(s).map_err(|_| NvError::InteriorNul)?)
+            }
+            "nvlist" => NvValue::NvList(pairs_from_json(v)?),
+            "byte_array" => NvValue::ByteArray(hex_decode(
+                v.as_str()
+                    .ok_or_else(|| invalid(format!("{} is not a hex string", v)))?,
+            )?),
+            "int8_array" => NvValue::Int8Array(num_array(v)?),
+            "uint8_array" => NvValue::Uint8Array(num_array(v)?),
+            "int16_array" => NvValue::Int16Array(num_array(v)?),
+            "uint16_array" => NvValue::Uint16Array(num_array(v)?),
+            "int32_array" => NvValue::Int32Array(num_array(v)?),
+            "uint32_array" => NvValue::Uint32Array(num_array(v)?),
+            "int64_array" => NvValue::Int64Array(num_array(v)?),
+            "uint64_array" => NvValue::Uint64Array(num_array(v)?),
+            "nvlist_array" => NvValue::NvListArray(
+                v.as_array()
+                    .ok_or_else(|| invalid(format!("{} is not an array", v)))?
+                    .iter()
+                    .map(pairs_from_json)
+                    .collect::<NvResult<_>>()?,
+            ),
+            _ => return Err(invalid(format!("unknown type tag {:?} for {}", tag, v))),
+        })
+    }
+}
+
+impl NvListRef {
+    /// Convert to a JSON object, tagging each value with its type
+    ///
+    /// See the [`json`](crate::json) module for the format.
+    pub fn to_json(&self) -> NvResult<Value> {
+        pairs_to_json(&pairs_of(self)?)
+    }
+}
+
+impl NvList {
+    /// Build an `NvList` (with the `NV_UNIQUE_NAME` constraint) from a JSON object produced by
+    /// [`NvListRef::to_json()`]
+    pub fn from_json(v: &Value) -> NvResult<NvList> {
+        let mut nv = NvList::try_new_unique_names()?;
+        for (name, value) in pairs_from_json(v)? {
+            nv.insert(name.as_c_str(), &value)?;
+        }
+        Ok(nv)
+    }
+}
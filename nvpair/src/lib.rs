@@ -1,12 +1,31 @@
 #![warn(missing_debug_implementations, rust_2018_idioms)]
 
-use cstr_argument::CStrArgument;
+pub use cstr_argument::CStrArgument;
 use foreign_types::{foreign_type, ForeignType, ForeignTypeRef, Opaque};
 use nvpair_sys as sys;
 use std::mem::MaybeUninit;
 use std::os::raw::c_int;
 use std::{ffi, fmt, io, ptr};
 
+pub mod alloc;
+mod cmp;
+mod error;
+#[cfg(feature = "json")]
+pub mod json;
+mod packed;
+mod pretty;
+mod value;
+
+pub use alloc::NvListBuilder;
+pub use cmp::{diff, NvDiff};
+use error::cstr;
+pub use error::{NvError, NvResult};
+#[cfg(feature = "derive")]
+pub use nvpair_derive::{NvDecode, NvEncode};
+pub use packed::{UnpackError, UnpackLimits};
+pub use pretty::Pretty;
+pub use value::NvValue;
+
 #[derive(Debug)]
 pub enum NvData<'a> {
     Unknown,
@@ -42,13 +61,13 @@ pub enum NvData<'a> {
 }
 
 pub trait NvEncode {
-    fn insert_into<S: CStrArgument>(&self, name: S, nv: &mut NvListRef) -> io::Result<()>;
-    //fn read(NvPair &nv) -> io::Result<Self>;
+    fn insert_into<S: CStrArgument>(&self, name: S, nv: &mut NvListRef) -> NvResult<()>;
+    //fn read(NvPair &nv) -> NvResult<Self>;
 }
 
 impl NvEncode for bool {
-    fn insert_into<S: CStrArgument>(&self, name: S, nv: &mut NvListRef) -> io::Result<()> {
-        let name = name.into_cstr();
+    fn insert_into<S: CStrArgument>(&self, name: S, nv: &mut NvListRef) -> NvResult<()> {
+        let name = cstr(name)?;
         let v = unsafe {
             sys::nvlist_add_boolean_value(
                 nv.as_mut_ptr(),
@@ -61,7 +80,7 @@ impl NvEncode for bool {
             )
         };
         if v != 0 {
-            Err(io::Error::from_raw_os_error(v))
+            Err(NvError::from_errno(v))
         } else {
             Ok(())
         }
@@ -69,11 +88,11 @@ impl NvEncode for bool {
 }
 
 impl NvEncode for i8 {
-    fn insert_into<S: CStrArgument>(&self, name: S, nv: &mut NvListRef) -> io::Result<()> {
-        let name = name.into_cstr();
+    fn insert_into<S: CStrArgument>(&self, name: S, nv: &mut NvListRef) -> NvResult<()> {
+        let name = cstr(name)?;
         let v = unsafe { sys::nvlist_add_int8(nv.as_mut_ptr(), name.as_ref().as_ptr(), *self) };
         if v != 0 {
-            Err(io::Error::from_raw_os_error(v))
+            Err(NvError::from_errno(v))
         } else {
             Ok(())
         }
@@ -81,11 +100,11 @@ impl NvEncode for i8 {
 }
 
 impl NvEncode for u8 {
-    fn insert_into<S: CStrArgument>(&self, name: S, nv: &mut NvListRef) -> io::Result<()> {
-        let name = name.into_cstr();
+    fn insert_into<S: CStrArgument>(&self, name: S, nv: &mut NvListRef) -> NvResult<()> {
+        let name = cstr(name)?;
         let v = unsafe { sys::nvlist_add_uint8(nv.as_mut_ptr(), name.as_ref().as_ptr(), *self) };
         if v != 0 {
-            Err(io::Error::from_raw_os_error(v))
+            Err(NvError::from_errno(v))
         } else {
             Ok(())
         }
@@ -93,11 +112,11 @@ impl NvEncode for u8 {
 }
 
 impl NvEncode for i16 {
-    fn insert_into<S: CStrArgument>(&self, name: S, nv: &mut NvListRef) -> io::Result<()> {
-        let name = name.into_cstr();
+    fn insert_into<S: CStrArgument>(&self, name: S, nv: &mut NvListRef) -> NvResult<()> {
+        let name = cstr(name)?;
         let v = unsafe { sys::nvlist_add_int16(nv.as_mut_ptr(), name.as_ref().as_ptr(), *self) };
         if v != 0 {
-            Err(io::Error::from_raw_os_error(v))
+            Err(NvError::from_errno(v))
         } else {
             Ok(())
         }
@@ -105,11 +124,11 @@ impl NvEncode for i16 {
 }
 
 impl NvEncode for u16 {
-    fn insert_into<S: CStrArgument>(&self, name: S, nv: &mut NvListRef) -> io::Result<()> {
-        let name = name.into_cstr();
+    fn insert_into<S: CStrArgument>(&self, name: S, nv: &mut NvListRef) -> NvResult<()> {
+        let name = cstr(name)?;
         let v = unsafe { sys::nvlist_add_uint16(nv.as_mut_ptr(), name.as_ref().as_ptr(), *self) };
         if v != 0 {
-            Err(io::Error::from_raw_os_error(v))
+            Err(NvError::from_errno(v))
         } else {
             Ok(())
         }
@@ -117,11 +136,11 @@ impl NvEncode for u16 {
 }
 
 impl NvEncode for i32 {
-    fn insert_into<S: CStrArgument>(&self, name: S, nv: &mut NvListRef) -> io::Result<()> {
-        let name = name.into_cstr();
+    fn insert_into<S: CStrArgument>(&self, name: S, nv: &mut NvListRef) -> NvResult<()> {
+        let name = cstr(name)?;
         let v = unsafe { sys::nvlist_add_int32(nv.as_mut_ptr(), name.as_ref().as_ptr(), *self) };
         if v != 0 {
-            Err(io::Error::from_raw_os_error(v))
+            Err(NvError::from_errno(v))
         } else {
             Ok(())
         }
@@ -129,11 +148,11 @@ impl NvEncode for i32 {
 }
 
 impl NvEncode for u32 {
-    fn insert_into<S: CStrArgument>(&self, name: S, nv: &mut NvListRef) -> io::Result<()> {
-        let name = name.into_cstr();
+    fn insert_into<S: CStrArgument>(&self, name: S, nv: &mut NvListRef) -> NvResult<()> {
+        let name = cstr(name)?;
         let v = unsafe { sys::nvlist_add_uint32(nv.as_mut_ptr(), name.as_ref().as_ptr(), *self) };
         if v != 0 {
-            Err(io::Error::from_raw_os_error(v))
+            Err(NvError::from_errno(v))
         } else {
             Ok(())
         }
@@ -141,11 +160,11 @@ impl NvEncode for u32 {
 }
 
 impl NvEncode for i64 {
-    fn insert_into<S: CStrArgument>(&self, name: S, nv: &mut NvListRef) -> io::Result<()> {
-        let name = name.into_cstr();
+    fn insert_into<S: CStrArgument>(&self, name: S, nv: &mut NvListRef) -> NvResult<()> {
+        let name = cstr(name)?;
         let v = unsafe { sys::nvlist_add_int64(nv.as_mut_ptr(), name.as_ref().as_ptr(), *self) };
         if v != 0 {
-            Err(io::Error::from_raw_os_error(v))
+            Err(NvError::from_errno(v))
         } else {
             Ok(())
         }
@@ -153,11 +172,11 @@ impl NvEncode for i64 {
 }
 
 impl NvEncode for u64 {
-    fn insert_into<S: CStrArgument>(&self, name: S, nv: &mut NvListRef) -> io::Result<()> {
-        let name = name.into_cstr();
+    fn insert_into<S: CStrArgument>(&self, name: S, nv: &mut NvListRef) -> NvResult<()> {
+        let name = cstr(name)?;
         let v = unsafe { sys::nvlist_add_uint64(nv.as_mut_ptr(), name.as_ref().as_ptr(), *self) };
         if v != 0 {
-            Err(io::Error::from_raw_os_error(v))
+            Err(NvError::from_errno(v))
         } else {
             Ok(())
         }
@@ -165,8 +184,8 @@ impl NvEncode for u64 {
 }
 
 impl NvEncode for [i8] {
-    fn insert_into<S: CStrArgument>(&self, name: S, nv: &mut NvListRef) -> io::Result<()> {
-        let name = name.into_cstr();
+    fn insert_into<S: CStrArgument>(&self, name: S, nv: &mut NvListRef) -> NvResult<()> {
+        let name = cstr(name)?;
         let v = unsafe {
             sys::nvlist_add_int8_array(
                 nv.as_mut_ptr(),
@@ -176,7 +195,7 @@ impl NvEncode for [i8] {
             )
         };
         if v != 0 {
-            Err(io::Error::from_raw_os_error(v))
+            Err(NvError::from_errno(v))
         } else {
             Ok(())
         }
@@ -184,8 +203,8 @@ impl NvEncode for [i8] {
 }
 
 impl NvEncode for [u8] {
-    fn insert_into<S: CStrArgument>(&self, name: S, nv: &mut NvListRef) -> io::Result<()> {
-        let name = name.into_cstr();
+    fn insert_into<S: CStrArgument>(&self, name: S, nv: &mut NvListRef) -> NvResult<()> {
+        let name = cstr(name)?;
         let v = unsafe {
             sys::nvlist_add_uint8_array(
                 nv.as_mut_ptr(),
@@ -195,7 +214,7 @@ impl NvEncode for [u8] {
             )
         };
         if v != 0 {
-            Err(io::Error::from_raw_os_error(v))
+            Err(NvError::from_errno(v))
         } else {
             Ok(())
         }
@@ -203,8 +222,8 @@ impl NvEncode for [u8] {
 }
 
 impl NvEncode for [i16] {
-    fn insert_into<S: CStrArgument>(&self, name: S, nv: &mut NvListRef) -> io::Result<()> {
-        let name = name.into_cstr();
+    fn insert_into<S: CStrArgument>(&self, name: S, nv: &mut NvListRef) -> NvResult<()> {
+        let name = cstr(name)?;
         let v = unsafe {
             sys::nvlist_add_int16_array(
                 nv.as_mut_ptr(),
@@ -214,7 +233,7 @@ impl NvEncode for [i16] {
             )
         };
         if v != 0 {
-            Err(io::Error::from_raw_os_error(v))
+            Err(NvError::from_errno(v))
         } else {
             Ok(())
         }
@@ -222,8 +241,8 @@ impl NvEncode for [i16] {
 }
 
 impl NvEncode for [u16] {
-    fn insert_into<S: CStrArgument>(&self, name: S, nv: &mut NvListRef) -> io::Result<()> {
-        let name = name.into_cstr();
+    fn insert_into<S: CStrArgument>(&self, name: S, nv: &mut NvListRef) -> NvResult<()> {
+        let name = cstr(name)?;
         let v = unsafe {
             sys::nvlist_add_uint16_array(
                 nv.as_mut_ptr(),
@@ -233,7 +252,7 @@ impl NvEncode for [u16] {
             )
         };
         if v != 0 {
-            Err(io::Error::from_raw_os_error(v))
+            Err(NvError::from_errno(v))
         } else {
             Ok(())
         }
@@ -241,8 +260,8 @@ impl NvEncode for [u16] {
 }
 
 impl NvEncode for [i32] {
-    fn insert_into<S: CStrArgument>(&self, name: S, nv: &mut NvListRef) -> io::Result<()> {
-        let name = name.into_cstr();
+    fn insert_into<S: CStrArgument>(&self, name: S, nv: &mut NvListRef) -> NvResult<()> {
+        let name = cstr(name)?;
         let v = unsafe {
             sys::nvlist_add_int32_array(
                 nv.as_mut_ptr(),
@@ -252,7 +271,7 @@ impl NvEncode for [i32] {
             )
         };
         if v != 0 {
-            Err(io::Error::from_raw_os_error(v))
+            Err(NvError::from_errno(v))
         } else {
             Ok(())
         }
@@ -260,8 +279,8 @@ impl NvEncode for [i32] {
 }
 
 impl NvEncode for [u32] {
-    fn insert_into<S: CStrArgument>(&self, name: S, nv: &mut NvListRef) -> io::Result<()> {
-        let name = name.into_cstr();
+    fn insert_into<S: CStrArgument>(&self, name: S, nv: &mut NvListRef) -> NvResult<()> {
+        let name = cstr(name)?;
         let v = unsafe {
             sys::nvlist_add_uint32_array(
                 nv.as_mut_ptr(),
@@ -271,7 +290,7 @@ impl NvEncode for [u32] {
             )
         };
         if v != 0 {
-            Err(io::Error::from_raw_os_error(v))
+            Err(NvError::from_errno(v))
         } else {
             Ok(())
         }
@@ -279,8 +298,8 @@ impl NvEncode for [u32] {
 }
 
 impl NvEncode for [i64] {
-    fn insert_into<S: CStrArgument>(&self, name: S, nv: &mut NvListRef) -> io::Result<()> {
-        let name = name.into_cstr();
+    fn insert_into<S: CStrArgument>(&self, name: S, nv: &mut NvListRef) -> NvResult<()> {
+        let name = cstr(name)?;
         let v = unsafe {
             sys::nvlist_add_int64_array(
                 nv.as_mut_ptr(),
@@ -290,7 +309,7 @@ impl NvEncode for [i64] {
             )
         };
         if v != 0 {
-            Err(io::Error::from_raw_os_error(v))
+            Err(NvError::from_errno(v))
         } else {
             Ok(())
         }
@@ -298,8 +317,8 @@ impl NvEncode for [i64] {
 }
 
 impl NvEncode for [u64] {
-    fn insert_into<S: CStrArgument>(&self, name: S, nv: &mut NvListRef) -> io::Result<()> {
-        let name = name.into_cstr();
+    fn insert_into<S: CStrArgument>(&self, name: S, nv: &mut NvListRef) -> NvResult<()> {
+        let name = cstr(name)?;
         let v = unsafe {
             sys::nvlist_add_uint64_array(
                 nv.as_mut_ptr(),
@@ -309,7 +328,7 @@ impl NvEncode for [u64] {
             )
         };
         if v != 0 {
-            Err(io::Error::from_raw_os_error(v))
+            Err(NvError::from_errno(v))
         } else {
             Ok(())
         }
@@ -317,13 +336,13 @@ impl NvEncode for [u64] {
 }
 
 impl NvEncode for ffi::CStr {
-    fn insert_into<S: CStrArgument>(&self, name: S, nv: &mut NvListRef) -> io::Result<()> {
-        let name = name.into_cstr();
+    fn insert_into<S: CStrArgument>(&self, name: S, nv: &mut NvListRef) -> NvResult<()> {
+        let name = cstr(name)?;
         let v = unsafe {
             sys::nvlist_add_string(nv.as_mut_ptr(), name.as_ref().as_ptr(), self.as_ptr())
         };
         if v != 0 {
-            Err(io::Error::from_raw_os_error(v))
+            Err(NvError::from_errno(v))
         } else {
             Ok(())
         }
@@ -331,14 +350,14 @@ impl NvEncode for ffi::CStr {
 }
 
 impl NvEncode for &str {
-    fn insert_into<S: CStrArgument>(&self, name: S, nv: &mut NvListRef) -> io::Result<()> {
-        std::ffi::CString::new(*self).unwrap().insert_into(name, nv)
+    fn insert_into<S: CStrArgument>(&self, name: S, nv: &mut NvListRef) -> NvResult<()> {
+        (**self).insert_into(name, nv)
     }
 }
 
 impl NvEncode for NvListRef {
-    fn insert_into<S: CStrArgument>(&self, name: S, nv: &mut NvListRef) -> io::Result<()> {
-        let name = name.into_cstr();
+    fn insert_into<S: CStrArgument>(&self, name: S, nv: &mut NvListRef) -> NvResult<()> {
+        let name = cstr(name)?;
         let v = unsafe {
             sys::nvlist_add_nvlist(
                 nv.as_mut_ptr(),
@@ -347,7 +366,7 @@ impl NvEncode for NvListRef {
             )
         };
         if v != 0 {
-            Err(io::Error::from_raw_os_error(v))
+            Err(NvError::from_errno(v))
         } else {
             Ok(())
         }
@@ -355,11 +374,11 @@ impl NvEncode for NvListRef {
 }
 
 impl NvEncode for () {
-    fn insert_into<S: CStrArgument>(&self, name: S, nv: &mut NvListRef) -> io::Result<()> {
-        let name = name.into_cstr();
+    fn insert_into<S: CStrArgument>(&self, name: S, nv: &mut NvListRef) -> NvResult<()> {
+        let name = cstr(name)?;
         let v = unsafe { sys::nvlist_add_boolean(nv.as_mut_ptr(), name.as_ref().as_ptr()) };
         if v != 0 {
-            Err(io::Error::from_raw_os_error(v))
+            Err(NvError::from_errno(v))
         } else {
             Ok(())
         }
@@ -367,8 +386,248 @@ impl NvEncode for () {
 }
 
 impl NvEncode for str {
-    fn insert_into<S: CStrArgument>(&self, name: S, nv: &mut NvListRef) -> io::Result<()> {
-        ffi::CString::new(self).unwrap().insert_into(name, nv)
+    fn insert_into<S: CStrArgument>(&self, name: S, nv: &mut NvListRef) -> NvResult<()> {
+        ffi::CString::new(self)
+            .map_err(|_| NvError::InteriorNul)?
+            .insert_into(name, nv)
+    }
+}
+
+impl NvEncode for String {
+    fn insert_into<S: CStrArgument>(&self, name: S, nv: &mut NvListRef) -> NvResult<()> {
+        self.as_str().insert_into(name, nv)
+    }
+}
+
+impl NvEncode for ffi::CString {
+    fn insert_into<S: CStrArgument>(&self, name: S, nv: &mut NvListRef) -> NvResult<()> {
+        self.as_c_str().insert_into(name, nv)
+    }
+}
+
+impl<T> NvEncode for Vec<T>
+where
+    [T]: NvEncode,
+{
+    fn insert_into<S: CStrArgument>(&self, name: S, nv: &mut NvListRef) -> NvResult<()> {
+        self[..].insert_into(name, nv)
+    }
+}
+
+impl NvEncode for NvList {
+    fn insert_into<S: CStrArgument>(&self, name: S, nv: &mut NvListRef) -> NvResult<()> {
+        (**self).insert_into(name, nv)
+    }
+}
+
+/// Insert `lists` as a single nvlist array. Each list is copied.
+fn add_nvlist_array<'a, S, I>(nv: &mut NvListRef, name: S, lists: I) -> NvResult<()>
+where
+    S: CStrArgument,
+    I: IntoIterator<Item = &'a NvListRef>,
+{
+    let mut ptrs: Vec<*mut sys::nvlist> = lists.into_iter().map(|l| l.as_ptr() as *mut _).collect();
+
+    let name = cstr(name)?;
+    let v = unsafe {
+        sys::nvlist_add_nvlist_array(
+            nv.as_mut_ptr(),
+            name.as_ref().as_ptr(),
+            ptrs.as_mut_ptr(),
+            ptrs.len() as u32,
+        )
+    };
+    if v != 0 {
+        Err(NvError::from_errno(v))
+    } else {
+        Ok(())
+    }
+}
+
+impl NvEncode for [NvList] {
+    fn insert_into<S: CStrArgument>(&self, name: S, nv: &mut NvListRef) -> NvResult<()> {
+        add_nvlist_array(nv, name, self.iter().map(|l| &**l))
+    }
+}
+
+impl NvEncode for [&NvListRef] {
+    fn insert_into<S: CStrArgument>(&self, name: S, nv: &mut NvListRef) -> NvResult<()> {
+        add_nvlist_array(nv, name, self.iter().copied())
+    }
+}
+
+/// Encodes each element as an nvlist, and inserts them as a single nvlist array
+impl<T: NvListEncode> NvEncode for [T] {
+    fn insert_into<S: CStrArgument>(&self, name: S, nv: &mut NvListRef) -> NvResult<()> {
+        let lists = self
+            .iter()
+            .map(|v| v.to_nvlist())
+            .collect::<NvResult<Vec<_>>>()?;
+        add_nvlist_array(nv, name, lists.iter().map(|l| &**l))
+    }
+}
+
+/// Build an [`NvList`] (with the `NV_UNIQUE_NAME` constraint) from `name => value` pairs
+///
+/// Values may be anything implementing [`NvEncode`], including other `nvlist!`s. A value written
+/// as `[a, b, ...]` is inserted as an array, so a list of `nvlist!`s becomes an nvlist array.
+///
+/// ```no_run
+/// # use nvpair::nvlist;
+/// let config = nvlist! {
+///     "version" => 5000u64,
+///     "name" => "tank",
+///     "vdev_tree" => nvlist! {
+///         "type" => "root",
+///         "children" => [
+///             nvlist! { "type" => "disk", "path" => "/dev/sda1" },
+///             nvlist! { "type" => "disk", "path" => "/dev/sdb1" },
+///         ],
+///     },
+/// };
+/// ```
+///
+/// Panics if inserting any of the values fails.
+#[macro_export]
+macro_rules! nvlist {
+    (@insert $nv:ident) => {};
+    (@insert $nv:ident $name:expr => [$($elem:expr),* $(,)?] $(, $($rest:tt)*)?) => {
+        $nv.insert($name, &[$($elem),*][..]).expect("nvlist!: insert failed");
+        $crate::nvlist!(@insert $nv $($($rest)*)?);
+    };
+    (@insert $nv:ident $name:expr => $value:expr $(, $($rest:tt)*)?) => {
+        $nv.insert($name, &$value).expect("nvlist!: insert failed");
+        $crate::nvlist!(@insert $nv $($($rest)*)?);
+    };
+    ($($body:tt)*) => {{
+        let mut nv = $crate::NvList::new_unique_names();
+        $crate::nvlist!(@insert nv $($body)*);
+        nv
+    }};
+}
+
+/// A type that is represented by an entire nvlist (typically a struct, with one pair per field)
+///
+/// Usually implemented via `#[derive(NvEncode)]` (from the `nvpair-derive` crate), which also
+/// provides an [`NvEncode`] impl that inserts the value as a nested nvlist.
+pub trait NvListEncode {
+    /// Insert each of our fields into `nv`
+    fn encode_into(&self, nv: &mut NvListRef) -> NvResult<()>;
+
+    /// Encode into a new `NvList` with the `NV_UNIQUE_NAME` constraint
+    fn to_nvlist(&self) -> NvResult<NvList> {
+        let mut nv = NvList::try_new_unique_names()?;
+        self.encode_into(&mut nv)?;
+        Ok(nv)
+    }
+}
+
+/// A type that can be decoded from the value of a single nvpair
+pub trait NvDecode: Sized {
+    fn decode(pair: &NvPair) -> NvResult<Self>;
+}
+
+/// A type that can be decoded from an entire nvlist. Counterpart of [`NvListEncode`].
+pub trait NvListDecode: Sized {
+    fn decode_from(nv: &NvListRef) -> NvResult<Self>;
+}
+
+fn type_mismatch(pair: &NvPair, expected: &'static str) -> NvError {
+    NvError::TypeMismatch {
+        name: pair.name().to_owned(),
+        expected,
+        found: pair.data_type().name(),
+    }
+}
+
+impl NvDecode for bool {
+    /// Accepts both `boolean_value` pairs and bare `boolean` (presence-only) pairs
+    fn decode(pair: &NvPair) -> NvResult<Self> {
+        match pair.data() {
+            NvData::BoolV(v) => Ok(v),
+            NvData::Bool => Ok(true),
+            _ => Err(type_mismatch(pair, "boolean_value")),
+        }
+    }
+}
+
+macro_rules! impl_nv_decode {
+    ($t:ty, $name:expr, $get:ident, $array_t:ty, $array_get:ident) => {
+        impl NvDecode for $t {
+            fn decode(pair: &NvPair) -> NvResult<Self> {
+                pair.$get().ok_or_else(|| type_mismatch(pair, $name))
+            }
+        }
+
+        impl NvDecode for $array_t {
+            fn decode(pair: &NvPair) -> NvResult<Self> {
+                match pair.$array_get() {
+                    Some(v) => Ok(v.to_vec()),
+                    None => Err(type_mismatch(pair, concat!($name, "_array"))),
+                }
+            }
+        }
+    };
+}
+
+impl_nv_decode!(i8, "int8", as_i8, Vec<i8>, as_i8_array);
+impl_nv_decode!(i16, "int16", as_i16, Vec<i16>, as_i16_array);
+impl_nv_decode!(u16, "uint16", as_u16, Vec<u16>, as_u16_array);
+impl_nv_decode!(i32, "int32", as_i32, Vec<i32>, as_i32_array);
+impl_nv_decode!(u32, "uint32", as_u32, Vec<u32>, as_u32_array);
+impl_nv_decode!(i64, "int64", as_i64, Vec<i64>, as_i64_array);
+impl_nv_decode!(u64, "uint64", as_u64, Vec<u64>, as_u64_array);
+
+impl NvDecode for u8 {
+    fn decode(pair: &NvPair) -> NvResult<Self> {
+        pair.as_u8()
+            .or_else(|| pair.as_byte())
+            .ok_or_else(|| type_mismatch(pair, "uint8"))
+    }
+}
+
+impl NvDecode for Vec<u8> {
+    fn decode(pair: &NvPair) -> NvResult<Self> {
+        match pair.as_u8_array().or_else(|| pair.as_byte_array()) {
+            Some(v) => Ok(v.to_vec()),
+            None => Err(type_mismatch(pair, "uint8_array")),
+        }
+    }
+}
+
+impl NvDecode for ffi::CString {
+    fn decode(pair: &NvPair) -> NvResult<Self> {
+        match pair.as_str() {
+            Some(v) => Ok(v.to_owned()),
+            None => Err(type_mismatch(pair, "string")),
+        }
+    }
+}
+
+impl NvDecode for String {
+    fn decode(pair: &NvPair) -> NvResult<Self> {
+        ffi::CString::decode(pair)?
+            .into_string()
+            .map_err(|e| NvError::Io(io::Error::new(io::ErrorKind::InvalidData, e)))
+    }
+}
+
+impl NvDecode for NvList {
+    fn decode(pair: &NvPair) -> NvResult<Self> {
+        match pair.as_list() {
+            Some(v) => v.try_to_owned(),
+            None => Err(type_mismatch(pair, "nvlist")),
+        }
+    }
+}
+
+/// Decodes each element of an nvlist array
+impl<T: NvListDecode> NvDecode for Vec<T> {
+    fn decode(pair: &NvPair) -> NvResult<Self> {
+        match pair.as_list_array() {
+            Some(v) => v.iter().map(|l| T::decode_from(l)).collect(),
+            None => Err(type_mismatch(pair, "nvlist_array")),
+        }
     }
 }
 
@@ -398,49 +657,58 @@ foreign_type! {
 impl NvList {
     /// Try to create a new `NvList` with no options.
     ///
-    /// Returns an error if memory allocation fails
+    /// Returns an error if memory allocation fails. See [`NvListBuilder`] for other options.
     #[doc(alias = "nvlist_alloc")]
-    pub fn try_new() -> io::Result<Self> {
-        let mut n = ptr::null_mut();
-        let v = unsafe {
-            // TODO: second arg is a bitfield of NV_UNIQUE_NAME|NV_UNIQUE_NAME_TYPE
-            sys::nvlist_alloc(&mut n, 0, 0)
-        };
-        if v != 0 {
-            Err(io::Error::from_raw_os_error(v))
-        } else {
-            Ok(unsafe { Self::from_ptr(n) })
-        }
+    pub fn try_new() -> NvResult<Self> {
+        NvListBuilder::new().build()
     }
 
     /// Try to create a new `NvList` with the `NV_UNIQUE_NAME` constraint
     ///
     /// Returns an error if memory allocation fails
     #[doc(alias = "nvlist_alloc")]
-    pub fn try_new_unique_names() -> io::Result<Self> {
-        let mut n = ptr::null_mut();
-        let v = unsafe { sys::nvlist_alloc(&mut n, sys::NV_UNIQUE_NAME, 0) };
-        if v != 0 {
-            Err(io::Error::from_raw_os_error(v))
-        } else {
-            Ok(unsafe { Self::from_ptr(n) })
-        }
+    pub fn try_new_unique_names() -> NvResult<Self> {
+        NvListBuilder::new().unique_names(true).build()
     }
 
     /// Try to create a new `NvList` from the packed buffer
     ///
-    /// Returns an error if memory allocation fails
-    pub fn try_unpack(buf: &[u8]) -> io::Result<Self> {
+    /// Returns an error if memory allocation fails, or [`NvError::Corrupt`] if `buf` isn't a valid
+    /// packed nvlist
+    pub fn try_unpack(buf: &[u8]) -> NvResult<Self> {
         let mut n = ptr::null_mut();
         let len_u64 = buf.len() as u64;
         let v = unsafe { nvpair_sys::nvlist_unpack(buf.as_ptr() as *mut _, len_u64, &mut n, 0) };
         if v != 0 {
-            Err(io::Error::from_raw_os_error(v))
+            // libnvpair reports malformed data with a handful of errnos, none of which say where
+            match NvError::from_errno(v) {
+                NvError::NoMemory => Err(NvError::NoMemory),
+                _ => Err(NvError::Corrupt { offset: None }),
+            }
         } else {
             Ok(unsafe { Self::from_ptr(n) })
         }
     }
 
+    /// Unpack a buffer from an untrusted source
+    ///
+    /// Unlike [`try_unpack()`](Self::try_unpack), the buffer is first checked to be a single, well
+    /// formed packed nvlist (with no trailing data) that stays within `limits`. Failures report
+    /// the offset of the problem within `buf`.
+    pub fn try_unpack_limited(buf: &[u8], limits: &UnpackLimits) -> Result<Self, UnpackError> {
+        packed::validate(buf, limits)?;
+        Self::try_unpack(buf).map_err(UnpackError::Unpack)
+    }
+
+    /// Read a single packed nvlist (as written by [`NvListRef::pack_into()`]) from `r`
+    ///
+    /// Packed nvlists don't record their total length, so this walks the headers of each pair to
+    /// find where the list ends. Only the bytes of the list are consumed, so `r` may hold other
+    /// data (or more lists) afterwards.
+    pub fn read_from<R: io::Read + ?Sized>(r: &mut R) -> NvResult<Self> {
+        Self::try_unpack(&packed::read_packed(r)?)
+    }
+
     /// Create a new `NvList` with no options
     ///
     /// # Panics
@@ -461,11 +729,11 @@ impl NvList {
         Self::try_new_unique_names().unwrap()
     }
 
-    pub fn try_clone(&self) -> io::Result<Self> {
+    pub fn try_clone(&self) -> NvResult<Self> {
         let mut n = ptr::null_mut();
         let v = unsafe { sys::nvlist_dup(self.as_ptr(), &mut n, 0) };
         if v != 0 {
-            Err(io::Error::from_raw_os_error(v))
+            Err(NvError::from_errno(v))
         } else {
             Ok(unsafe { Self::from_ptr(n) })
         }
@@ -484,6 +752,41 @@ impl Clone for NvList {
     }
 }
 
+/// Collect `(name, value)` pairs into a new list with the `NV_UNIQUE_NAME` constraint
+///
+/// ```no_run
+/// let props: nvpair::NvList = vec![("compression", "lz4"), ("atime", "off")]
+///     .into_iter()
+///     .collect();
+/// ```
+///
+/// Panics if inserting any of the pairs fails. Use [`NvListRef::insert()`] to handle errors.
+impl<K: CStrArgument, V: NvEncode> std::iter::FromIterator<(K, V)> for NvList {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut nv = NvList::new_unique_names();
+        nv.extend(iter);
+        nv
+    }
+}
+
+/// Insert each `(name, value)` pair, as with [`NvListRef::insert()`]
+///
+/// Panics if inserting any of the pairs fails.
+impl<K: CStrArgument, V: NvEncode> Extend<(K, V)> for NvListRef {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        for (name, value) in iter {
+            self.insert(name, &value)
+                .expect("NvList::extend: insert failed");
+        }
+    }
+}
+
+impl<K: CStrArgument, V: NvEncode> Extend<(K, V)> for NvList {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        (**self).extend(iter)
+    }
+}
+
 impl NvListRef {
     /// # Safety
     ///
@@ -508,18 +811,18 @@ impl NvListRef {
         unsafe { std::mem::transmute::<&NvListRef, *const sys::nvlist>(self) }
     }
 
-    pub fn encoded_size(&self, encoding: NvEncoding) -> io::Result<u64> {
+    pub fn encoded_size(&self, encoding: NvEncoding) -> NvResult<u64> {
         let mut l = 0u64;
         let v = unsafe { sys::nvlist_size(self.as_ptr() as *mut _, &mut l, encoding.as_raw()) };
         if v != 0 {
-            Err(io::Error::from_raw_os_error(v))
+            Err(NvError::from_errno(v))
         } else {
             Ok(l)
         }
     }
 
-    pub fn pack(&self, code: NvEncoding) -> io::Result<Vec<u8>> {
-        let size = self.encoded_size(code).unwrap() as usize;
+    pub fn pack(&self, code: NvEncoding) -> NvResult<Vec<u8>> {
+        let size = self.encoded_size(code)? as usize;
         let mut vec = Vec::with_capacity(size);
         let mut cap = vec.capacity() as u64;
         let mut ptr = vec.as_mut_ptr() as *mut i8;
@@ -536,22 +839,32 @@ impl NvListRef {
             v
         };
         if v != 0 {
-            Err(io::Error::from_raw_os_error(v))
+            Err(NvError::from_errno(v))
         } else {
             Ok(vec)
         }
     }
 
+    /// Pack this list and write it to `w`, returning the number of bytes written
+    ///
+    /// libnvpair can only pack into a single buffer, so the packed list is still built in memory
+    /// first.
+    pub fn pack_into<W: io::Write + ?Sized>(&self, w: &mut W, code: NvEncoding) -> NvResult<usize> {
+        let buf = self.pack(code)?;
+        w.write_all(&buf)?;
+        Ok(buf.len())
+    }
+
     pub fn is_empty(&self) -> bool {
         let v = unsafe { sys::nvlist_empty(self.as_ptr() as *mut _) };
         v != sys::boolean_t::B_FALSE
     }
 
-    pub fn add_boolean<S: CStrArgument>(&mut self, name: S) -> io::Result<()> {
-        let name = name.into_cstr();
+    pub fn add_boolean<S: CStrArgument>(&mut self, name: S) -> NvResult<()> {
+        let name = cstr(name)?;
         let v = unsafe { sys::nvlist_add_boolean(self.as_mut_ptr(), name.as_ref().as_ptr()) };
         if v != 0 {
-            Err(io::Error::from_raw_os_error(v))
+            Err(NvError::from_errno(v))
         } else {
             Ok(())
         }
@@ -574,52 +887,103 @@ impl NvListRef {
     }
 
     pub fn exists<S: CStrArgument>(&self, name: S) -> bool {
-        let name = name.into_cstr();
+        let name = match cstr(name) {
+            Ok(name) => name,
+            Err(_) => return false,
+        };
         let v = unsafe { sys::nvlist_exists(self.as_ptr() as *mut _, name.as_ref().as_ptr()) };
         v != sys::boolean_t::B_FALSE
     }
 
-    /*
-    // not allowed because `pair` is borrowed from `self`. Need to fiddle around so that we can
-    // check:
-    //  - `pair` is from `self`
-    //  - `pair` is the only outstanding reference to this pair (need by-value semantics)
-    pub fn remove(&mut self, pair: &NvPair) -> io::Result<()>
-    {
-        let v = unsafe { sys::nvlist_remove_nvpair(self.as_mut_ptr(), pair.as_ptr())};
+    /// Remove the pair named `name`
+    ///
+    /// If the list doesn't have the `NV_UNIQUE_NAME` constraint and holds more than one pair
+    /// with this name, only the first is removed. Returns [`NvError::NotFound`] if there is no such
+    /// pair.
+    pub fn remove<S: CStrArgument>(&mut self, name: S) -> NvResult<()> {
+        let name = cstr(name)?;
+        // `nvlist_lookup_nvpair()` only works on lists with `NV_UNIQUE_NAME`
+        let np = self.find(name.as_ref().to_bytes())?.as_ptr();
+        let v = unsafe { sys::nvlist_remove_nvpair(self.as_mut_ptr(), np) };
         if v != 0 {
-            Err(io::Error::from_raw_os_error(v))
+            Err(NvError::from_errno(v))
         } else {
             Ok(())
         }
     }
-    */
 
-    pub fn lookup<S: CStrArgument>(&self, name: S) -> io::Result<&NvPair> {
-        let name = name.into_cstr();
+    /// Remove every pair named `name`, of any type
+    ///
+    /// Returns [`NvError::NotFound`] if there were none.
+    pub fn remove_all<S: CStrArgument>(&mut self, name: S) -> NvResult<()> {
+        let name = cstr(name)?;
+        let v = unsafe { sys::nvlist_remove_all(self.as_mut_ptr(), name.as_ref().as_ptr()) };
+        if v != 0 {
+            Err(NvError::lookup(self, name.as_ref(), v, "any type"))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Only keep the pairs for which `f` returns `true`, removing the others
+    pub fn retain<F: FnMut(&NvPair) -> bool>(&mut self, mut f: F) -> NvResult<()> {
+        let mut np = unsafe { sys::nvlist_next_nvpair(self.as_mut_ptr(), ptr::null_mut()) };
+        while !np.is_null() {
+            // find the next pair before `np` might be freed
+            let next = unsafe { sys::nvlist_next_nvpair(self.as_mut_ptr(), np) };
+            if !f(unsafe { NvPair::from_ptr(np) }) {
+                let v = unsafe { sys::nvlist_remove_nvpair(self.as_mut_ptr(), np) };
+                if v != 0 {
+                    return Err(NvError::from_errno(v));
+                }
+            }
+            np = next;
+        }
+        Ok(())
+    }
+
+    /// Copy all the pairs of `other` into this list
+    ///
+    /// Pairs are added as with [`insert()`](Self::insert): when this list has the
+    /// `NV_UNIQUE_NAME` constraint, pairs of `other` replace any existing pairs with the same name.
+    pub fn merge(&mut self, other: &NvListRef) -> NvResult<()> {
+        let v = unsafe { sys::nvlist_merge(self.as_mut_ptr(), other.as_ptr() as *mut _, 0) };
+        if v != 0 {
+            Err(NvError::from_errno(v))
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn lookup<S: CStrArgument>(&self, name: S) -> NvResult<&NvPair> {
+        let name = cstr(name)?;
         let mut n = ptr::null_mut();
         let v = unsafe {
             sys::nvlist_lookup_nvpair(self.as_ptr() as *mut _, name.as_ref().as_ptr(), &mut n)
         };
         if v != 0 {
-            Err(io::Error::from_raw_os_error(v))
+            Err(NvError::lookup(self, name.as_ref(), v, "any type"))
         } else {
             Ok(unsafe { NvPair::from_ptr(n) })
         }
     }
 
-    pub fn try_to_owned(&self) -> io::Result<NvList> {
+    pub fn try_to_owned(&self) -> NvResult<NvList> {
         let mut n = MaybeUninit::uninit();
         let v = unsafe { sys::nvlist_dup(self.as_ptr() as *mut _, n.as_mut_ptr(), 0) };
         if v != 0 {
-            Err(io::Error::from_raw_os_error(v))
+            Err(NvError::from_errno(v))
         } else {
             Ok(unsafe { NvList::from_ptr(n.assume_init()) })
         }
     }
 
-    pub fn lookup_nvlist<S: CStrArgument>(&self, name: S) -> io::Result<NvList> {
-        let name = name.into_cstr();
+    /// Look up a nested nvlist
+    ///
+    /// The returned list is owned by `self`. Use [`NvListRef::try_to_owned()`] to get an
+    /// independent copy.
+    pub fn lookup_nvlist<S: CStrArgument>(&self, name: S) -> NvResult<&NvListRef> {
+        let name = cstr(name)?;
 
         let mut n = MaybeUninit::uninit();
         let v = unsafe {
@@ -630,15 +994,29 @@ impl NvListRef {
             )
         };
         if v != 0 {
-            Err(io::Error::from_raw_os_error(v))
+            Err(NvError::lookup(self, name.as_ref(), v, "nvlist"))
         } else {
-            let r = unsafe { NvList::from_ptr(n.assume_init()) };
-            Ok(r)
+            Ok(unsafe { NvListRef::from_ptr(n.assume_init()) })
+        }
+    }
+
+    /// Look up a nested nvlist, allowing it to be modified in place
+    pub fn lookup_nvlist_mut<S: CStrArgument>(&mut self, name: S) -> NvResult<&mut NvListRef> {
+        let name = cstr(name)?;
+
+        let mut n = MaybeUninit::uninit();
+        let v = unsafe {
+            sys::nvlist_lookup_nvlist(self.as_mut_ptr(), name.as_ref().as_ptr(), n.as_mut_ptr())
+        };
+        if v != 0 {
+            Err(NvError::lookup(self, name.as_ref(), v, "nvlist"))
+        } else {
+            Ok(unsafe { NvListRef::from_mut_ptr(n.assume_init()) })
         }
     }
 
-    pub fn lookup_string<S: CStrArgument>(&self, name: S) -> io::Result<ffi::CString> {
-        let name = name.into_cstr();
+    pub fn lookup_string<S: CStrArgument>(&self, name: S) -> NvResult<ffi::CString> {
+        let name = cstr(name)?;
         let mut n = MaybeUninit::uninit();
         let v = unsafe {
             sys::nvlist_lookup_string(
@@ -649,15 +1027,15 @@ impl NvListRef {
         };
 
         if v != 0 {
-            Err(io::Error::from_raw_os_error(v))
+            Err(NvError::lookup(self, name.as_ref(), v, "string"))
         } else {
             let s = unsafe { ffi::CStr::from_ptr(n.assume_init()).to_owned() };
             Ok(s)
         }
     }
 
-    pub fn lookup_uint64<S: CStrArgument>(&self, name: S) -> io::Result<u64> {
-        let name = name.into_cstr();
+    pub fn lookup_uint64<S: CStrArgument>(&self, name: S) -> NvResult<u64> {
+        let name = cstr(name)?;
         let mut n = MaybeUninit::uninit();
         let v = unsafe {
             sys::nvlist_lookup_uint64(
@@ -667,14 +1045,17 @@ impl NvListRef {
             )
         };
         if v != 0 {
-            Err(io::Error::from_raw_os_error(v))
+            Err(NvError::lookup(self, name.as_ref(), v, "uint64"))
         } else {
             Ok(unsafe { n.assume_init() })
         }
     }
 
-    pub fn lookup_nvlist_array<S: CStrArgument>(&self, name: S) -> io::Result<Vec<NvList>> {
-        let name = name.into_cstr();
+    /// Look up an array of nested nvlists
+    ///
+    /// As with [`lookup_nvlist()`], the returned lists are owned by `self`.
+    pub fn lookup_nvlist_array<S: CStrArgument>(&self, name: S) -> NvResult<Vec<&NvListRef>> {
+        let name = cstr(name)?;
         let mut n = ptr::null_mut();
         let mut len = 0;
         let v = unsafe {
@@ -686,12 +1067,12 @@ impl NvListRef {
             )
         };
         if v != 0 {
-            Err(io::Error::from_raw_os_error(v))
+            Err(NvError::lookup(self, name.as_ref(), v, "nvlist_array"))
         } else {
             let r = unsafe {
                 std::slice::from_raw_parts(n, len as usize)
                     .iter()
-                    .map(|x| NvList::from_ptr(*x))
+                    .map(|x| NvListRef::from_ptr(*x))
                     .collect()
             };
 
@@ -699,8 +1080,8 @@ impl NvListRef {
         }
     }
 
-    pub fn lookup_uint64_array<S: CStrArgument>(&self, name: S) -> io::Result<Vec<u64>> {
-        let name = name.into_cstr();
+    pub fn lookup_uint64_array<S: CStrArgument>(&self, name: S) -> NvResult<Vec<u64>> {
+        let name = cstr(name)?;
 
         let mut n = ptr::null_mut();
         let mut len = 0;
@@ -714,7 +1095,7 @@ impl NvListRef {
         };
 
         if v != 0 {
-            Err(io::Error::from_raw_os_error(v))
+            Err(NvError::lookup(self, name.as_ref(), v, "uint64_array"))
         } else {
             let r = unsafe { ::std::slice::from_raw_parts(n, len as usize).to_vec() };
 
@@ -727,9 +1108,151 @@ impl NvListRef {
         &mut self,
         name: S,
         data: &D,
-    ) -> io::Result<()> {
+    ) -> NvResult<()> {
         data.insert_into(name, self)
     }
+
+    /// Insert `data` as `name`, first removing any pairs that already have that name
+    ///
+    /// For lists with the `NV_UNIQUE_NAME` constraint this is the same as [`insert()`](Self::insert),
+    /// which already replaces pairs of the same name. Other lists would otherwise gain a second
+    /// pair.
+    pub fn insert_or_replace<S: CStrArgument, D: NvEncode + ?Sized>(
+        &mut self,
+        name: S,
+        data: &D,
+    ) -> NvResult<()> {
+        let name = cstr(name)?;
+        match self.remove_all(name.as_ref()) {
+            Ok(()) | Err(NvError::NotFound { .. }) => {}
+            Err(e) => return Err(e),
+        }
+        self.insert(name.as_ref(), data)
+    }
+
+    /// Look up a value nested within this list (and the lists inside it)
+    ///
+    /// `path` is a `/` separated list of pair names. When a component names an array (of nvlists
+    /// or of any other type), the next component is the index of an element within that array.
+    /// For example, `"vdev_tree/children/0/path"` finds the `path` of the first child vdev in a
+    /// pool config.
+    ///
+    /// If a list contains more than one pair with the same name, the first is used. Pair names
+    /// that themselves contain `/` can't be reached.
+    pub fn lookup_path(&self, path: &str) -> NvResult<NvData<'_>> {
+        let mut cur = NvData::NvListRef(self);
+        let mut prev = "";
+        for comp in path.split('/').filter(|c| !c.is_empty()) {
+            cur = match cur {
+                NvData::NvListRef(l) => l.find(comp.as_bytes())?.data(),
+                array => array_element(prev, &array, comp)?,
+            };
+            prev = comp;
+        }
+        Ok(cur)
+    }
+
+    /// Like [`lookup_path()`], but returns the nvlist found at `path` mutably so that its pairs
+    /// may be modified in place
+    ///
+    /// Every component of `path` must be an nvlist, or an index into an nvlist array.
+    pub fn lookup_path_mut(&mut self, path: &str) -> NvResult<&mut NvListRef> {
+        let mut comps = path.split('/').filter(|c| !c.is_empty());
+        let mut cur = self;
+        while let Some(comp) = comps.next() {
+            let pair = cur.find(comp.as_bytes())?.as_ptr();
+            let next = match unsafe { sys::nvpair_type(pair) } {
+                sys::data_type_t::DATA_TYPE_NVLIST => unsafe {
+                    let mut l = MaybeUninit::uninit();
+                    sys::nvpair_value_nvlist(pair, l.as_mut_ptr());
+                    l.assume_init()
+                },
+                sys::data_type_t::DATA_TYPE_NVLIST_ARRAY => {
+                    let idx = match comps.next() {
+                        Some(idx) => path_index(idx)?,
+                        None => return Err(path_type_mismatch(comp, "nvlist", "nvlist_array")),
+                    };
+                    let (array, len) = unsafe {
+                        let mut array = MaybeUninit::uninit();
+                        let mut len = MaybeUninit::uninit();
+                        sys::nvpair_value_nvlist_array(pair, array.as_mut_ptr(), len.as_mut_ptr());
+                        (array.assume_init(), len.assume_init() as usize)
+                    };
+                    if idx >= len {
+                        return Err(NvError::not_found(idx.to_string()));
+                    }
+                    unsafe { *array.add(idx) }
+                }
+                _ => {
+                    let found = unsafe { NvPair::from_ptr(pair) }.data_type().name();
+                    return Err(path_type_mismatch(comp, "nvlist", found));
+                }
+            };
+            cur = unsafe { NvListRef::from_mut_ptr(next) };
+        }
+        Ok(cur)
+    }
+
+    /// Insert `data` at `path`, creating or replacing the last component of `path` in the nvlist
+    /// named by the rest of it (see [`lookup_path_mut()`])
+    ///
+    /// As with [`insert()`], an existing pair is only replaced if the containing list has the
+    /// `NV_UNIQUE_NAME` constraint.
+    pub fn insert_path<D: NvEncode + ?Sized>(&mut self, path: &str, data: &D) -> NvResult<()> {
+        let path = path.trim_end_matches('/');
+        let (parent, name) = match path.rfind('/') {
+            Some(i) => (&path[..i], &path[i + 1..]),
+            None => ("", path),
+        };
+        if name.is_empty() {
+            return Err(NvError::invalid_input("path has no name to insert"));
+        }
+        self.lookup_path_mut(parent)?.insert(name, data)
+    }
+
+    /// The first pair named `name`
+    ///
+    /// Unlike [`lookup()`](Self::lookup), this doesn't require `NV_UNIQUE_NAME`: it walks the
+    /// list, so when several pairs share a name, the first is returned. Path lookups,
+    /// [`remove()`](Self::remove) and the derived `NvDecode` also find pairs this way.
+    pub fn find<S: AsRef<[u8]>>(&self, name: S) -> NvResult<&NvPair> {
+        let name = name.as_ref();
+        self.iter()
+            .find(|p| p.name().to_bytes() == name)
+            .ok_or_else(|| NvError::not_found(name))
+    }
+}
+
+/// Array elements are named by their index
+fn path_index(comp: &str) -> NvResult<usize> {
+    comp.parse().map_err(|_| NvError::not_found(comp))
+}
+
+fn path_type_mismatch(comp: &str, expected: &'static str, found: &'static str) -> NvError {
+    NvError::TypeMismatch {
+        name: ffi::CString::new(comp).unwrap_or_default(),
+        expected,
+        found,
+    }
+}
+
+/// Element `comp` of the array named `name`
+fn array_element<'a>(name: &str, array: &NvData<'a>, comp: &str) -> NvResult<NvData<'a>> {
+    let idx = path_index(comp)?;
+    let e = match array {
+        NvData::ByteArray(v) => v.get(idx).map(|v| NvData::Byte(*v)),
+        NvData::Int8Array(v) => v.get(idx).map(|v| NvData::Int8(*v)),
+        NvData::Uint8Array(v) => v.get(idx).map(|v| NvData::Uint8(*v)),
+        NvData::Int16Array(v) => v.get(idx).map(|v| NvData::Int16(*v)),
+        NvData::Uint16Array(v) => v.get(idx).map(|v| NvData::Uint16(*v)),
+        NvData::Int32Array(v) => v.get(idx).map(|v| NvData::Int32(*v)),
+        NvData::Uint32Array(v) => v.get(idx).map(|v| NvData::Uint32(*v)),
+        NvData::Int64Array(v) => v.get(idx).map(|v| NvData::Int64(*v)),
+        NvData::Uint64Array(v) => v.get(idx).map(|v| NvData::Uint64(*v)),
+        NvData::NvListRefArray(v) => v.get(idx).map(|v| NvData::NvListRef(v)),
+        d => return Err(path_type_mismatch(name, "array", d.type_name())),
+    };
+    e.ok_or_else(|| NvError::not_found(comp))
 }
 
 impl std::fmt::Debug for NvList {
@@ -798,6 +1321,157 @@ impl<'a> Iterator for NvListIter<'a> {
     }
 }
 
+/// A typed getter for a pair holding a single value, which returns `None` if the pair has another
+/// type. The value is read either by the named `nvpair_value_*()` function, or by a block given
+/// the `*mut nvpair_t`.
+macro_rules! nvpair_value {
+    ($(#[$m:meta])* $name:ident, $t:ty, $dt:ident, $f:ident) => {
+        nvpair_value!($(#[$m])* $name, $t, $dt, |p| {
+            let mut v = MaybeUninit::uninit();
+            sys::$f(p, v.as_mut_ptr());
+            v.assume_init()
+        });
+    };
+    ($(#[$m:meta])* $name:ident, $t:ty, $dt:ident, |$p:ident| $body:block) => {
+        $(#[$m])*
+        pub fn $name(&self) -> Option<$t> {
+            if self.raw_type() != sys::data_type_t::$dt {
+                return None;
+            }
+            let $p = self.as_ptr();
+            Some(unsafe { $body })
+        }
+    };
+}
+
+/// Like `nvpair_value!`, for array types, which borrow from the pair
+macro_rules! nvpair_array {
+    ($(#[$m:meta])* $name:ident, $t:ty, $dt:ident, $f:ident) => {
+        $(#[$m])*
+        pub fn $name(&self) -> Option<&[$t]> {
+            if self.raw_type() != sys::data_type_t::$dt {
+                return None;
+            }
+            Some(unsafe {
+                let mut array = MaybeUninit::uninit();
+                let mut len = MaybeUninit::uninit();
+                sys::$f(self.as_ptr(), array.as_mut_ptr(), len.as_mut_ptr());
+                let len = len.assume_init() as usize;
+                if len == 0 {
+                    &[]
+                } else {
+                    std::slice::from_raw_parts(array.assume_init() as *const $t, len)
+                }
+            })
+        }
+    };
+}
+
+/// The type of an nvpair's data (`data_type_t`), as returned by [`NvPair::data_type()`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NvType {
+    Boolean,
+    BooleanValue,
+    Byte,
+    Int8,
+    Uint8,
+    Int16,
+    Uint16,
+    Int32,
+    Uint32,
+    Int64,
+    Uint64,
+    String,
+    NvList,
+    ByteArray,
+    Int8Array,
+    Uint8Array,
+    Int16Array,
+    Uint16Array,
+    Int32Array,
+    Uint32Array,
+    Int64Array,
+    Uint64Array,
+    NvListArray,
+    StringArray,
+    BooleanArray,
+    Hrtime,
+    Double,
+    /// A type this library doesn't know about, with its raw `data_type_t` value
+    Unknown(i32),
+}
+
+impl NvType {
+    fn from_raw(t: sys::data_type_t::Type) -> Self {
+        use sys::data_type_t::*;
+        match t {
+            DATA_TYPE_BOOLEAN => NvType::Boolean,
+            DATA_TYPE_BOOLEAN_VALUE => NvType::BooleanValue,
+            DATA_TYPE_BYTE => NvType::Byte,
+            DATA_TYPE_INT8 => NvType::Int8,
+            DATA_TYPE_UINT8 => NvType::Uint8,
+            DATA_TYPE_INT16 => NvType::Int16,
+            DATA_TYPE_UINT16 => NvType::Uint16,
+            DATA_TYPE_INT32 => NvType::Int32,
+            DATA_TYPE_UINT32 => NvType::Uint32,
+            DATA_TYPE_INT64 => NvType::Int64,
+            DATA_TYPE_UINT64 => NvType::Uint64,
+            DATA_TYPE_STRING => NvType::String,
+            DATA_TYPE_NVLIST => NvType::NvList,
+            DATA_TYPE_BYTE_ARRAY => NvType::ByteArray,
+            DATA_TYPE_INT8_ARRAY => NvType::Int8Array,
+            DATA_TYPE_UINT8_ARRAY => NvType::Uint8Array,
+            DATA_TYPE_INT16_ARRAY => NvType::Int16Array,
+            DATA_TYPE_UINT16_ARRAY => NvType::Uint16Array,
+            DATA_TYPE_INT32_ARRAY => NvType::Int32Array,
+            DATA_TYPE_UINT32_ARRAY => NvType::Uint32Array,
+            DATA_TYPE_INT64_ARRAY => NvType::Int64Array,
+            DATA_TYPE_UINT64_ARRAY => NvType::Uint64Array,
+            DATA_TYPE_NVLIST_ARRAY => NvType::NvListArray,
+            DATA_TYPE_STRING_ARRAY => NvType::StringArray,
+            DATA_TYPE_BOOLEAN_ARRAY => NvType::BooleanArray,
+            DATA_TYPE_HRTIME => NvType::Hrtime,
+            DATA_TYPE_DOUBLE => NvType::Double,
+            t => NvType::Unknown(t),
+        }
+    }
+
+    /// The name of this type, as used by libnvpair (`data_type_t`) without the `DATA_TYPE_`
+    /// prefix: `"uint64"`, `"nvlist_array"`, etc.
+    pub fn name(&self) -> &'static str {
+        match self {
+            NvType::Boolean => "boolean",
+            NvType::BooleanValue => "boolean_value",
+            NvType::Byte => "byte",
+            NvType::Int8 => "int8",
+            NvType::Uint8 => "uint8",
+            NvType::Int16 => "int16",
+            NvType::Uint16 => "uint16",
+            NvType::Int32 => "int32",
+            NvType::Uint32 => "uint32",
+            NvType::Int64 => "int64",
+            NvType::Uint64 => "uint64",
+            NvType::String => "string",
+            NvType::NvList => "nvlist",
+            NvType::ByteArray => "byte_array",
+            NvType::Int8Array => "int8_array",
+            NvType::Uint8Array => "uint8_array",
+            NvType::Int16Array => "int16_array",
+            NvType::Uint16Array => "uint16_array",
+            NvType::Int32Array => "int32_array",
+            NvType::Uint32Array => "uint32_array",
+            NvType::Int64Array => "int64_array",
+            NvType::Uint64Array => "uint64_array",
+            NvType::NvListArray => "nvlist_array",
+            NvType::StringArray => "string_array",
+            NvType::BooleanArray => "boolean_array",
+            NvType::Hrtime => "hrtime",
+            NvType::Double => "double",
+            NvType::Unknown(_) => "unknown",
+        }
+    }
+}
+
 pub struct NvPair(Opaque);
 unsafe impl ForeignTypeRef for NvPair {
     type CType = sys::nvpair;
@@ -808,276 +1482,231 @@ impl NvPair {
         unsafe { ffi::CStr::from_ptr(sys::nvpair_name(self.as_ptr())) }
     }
 
-    // TODO: consider defering decode here until actually requested by the caller. Users of
-    // `data` might not care to decode most data types, meaning we're wasting time with the
-    // various `nvpair_value_*()` calls some of the time.
-    pub fn data(&self) -> NvData<'_> {
-        let data_type = unsafe { sys::nvpair_type(self.as_ptr()) };
-
-        match data_type {
-            sys::data_type_t::DATA_TYPE_BOOLEAN => NvData::Bool,
-            sys::data_type_t::DATA_TYPE_BOOLEAN_VALUE => {
-                let v = unsafe {
-                    let mut v = MaybeUninit::uninit();
-                    sys::nvpair_value_boolean_value(self.as_ptr(), v.as_mut_ptr());
-                    v.assume_init()
-                };
-
-                NvData::BoolV(v == sys::boolean_t::B_TRUE)
-            }
-            sys::data_type_t::DATA_TYPE_BYTE => {
-                let v = unsafe {
-                    let mut v = MaybeUninit::uninit();
-                    sys::nvpair_value_byte(self.as_ptr(), v.as_mut_ptr());
-                    v.assume_init()
-                };
-
-                NvData::Byte(v)
-            }
-            sys::data_type_t::DATA_TYPE_INT8 => {
-                let v = unsafe {
-                    let mut v = MaybeUninit::uninit();
-                    sys::nvpair_value_int8(self.as_ptr(), v.as_mut_ptr());
-                    v.assume_init()
-                };
-
-                NvData::Int8(v)
-            }
-            sys::data_type_t::DATA_TYPE_UINT8 => {
-                let v = unsafe {
-                    let mut v = MaybeUninit::uninit();
-                    sys::nvpair_value_uint8(self.as_ptr(), v.as_mut_ptr());
-                    v.assume_init()
-                };
-
-                NvData::Uint8(v)
-            }
-            sys::data_type_t::DATA_TYPE_INT16 => {
-                let v = unsafe {
-                    let mut v = MaybeUninit::uninit();
-                    sys::nvpair_value_int16(self.as_ptr(), v.as_mut_ptr());
-                    v.assume_init()
-                };
-
-                NvData::Int16(v)
-            }
-            sys::data_type_t::DATA_TYPE_UINT16 => {
-                let v = unsafe {
-                    let mut v = MaybeUninit::uninit();
-                    sys::nvpair_value_uint16(self.as_ptr(), v.as_mut_ptr());
-                    v.assume_init()
-                };
-
-                NvData::Uint16(v)
-            }
-            sys::data_type_t::DATA_TYPE_INT32 => {
-                let v = unsafe {
-                    let mut v = MaybeUninit::uninit();
-                    sys::nvpair_value_int32(self.as_ptr(), v.as_mut_ptr());
-                    v.assume_init()
-                };
-
-                NvData::Int32(v)
-            }
-            sys::data_type_t::DATA_TYPE_UINT32 => {
-                let v = unsafe {
-                    let mut v = MaybeUninit::uninit();
-                    sys::nvpair_value_uint32(self.as_ptr(), v.as_mut_ptr());
-                    v.assume_init()
-                };
-
-                NvData::Uint32(v)
-            }
-            sys::data_type_t::DATA_TYPE_INT64 => {
-                let v = unsafe {
-                    let mut v = MaybeUninit::uninit();
-                    sys::nvpair_value_int64(self.as_ptr(), v.as_mut_ptr());
-                    v.assume_init()
-                };
-
-                NvData::Int64(v)
-            }
-            sys::data_type_t::DATA_TYPE_UINT64 => {
-                let v = unsafe {
-                    let mut v = MaybeUninit::uninit();
-                    sys::nvpair_value_uint64(self.as_ptr(), v.as_mut_ptr());
-                    v.assume_init()
-                };
-
-                NvData::Uint64(v)
-            }
-            sys::data_type_t::DATA_TYPE_STRING => {
-                let s = unsafe {
-                    let mut n = MaybeUninit::uninit();
-                    sys::nvpair_value_string(self.as_ptr(), n.as_mut_ptr());
-                    ffi::CStr::from_ptr(n.assume_init())
-                };
-
-                NvData::Str(s)
-            }
-            sys::data_type_t::DATA_TYPE_NVLIST => {
-                let l = unsafe {
-                    let mut l = MaybeUninit::uninit();
-                    sys::nvpair_value_nvlist(self.as_ptr(), l.as_mut_ptr());
-                    NvListRef::from_ptr(l.assume_init())
-                };
+    /// The type of this pair's data, without decoding it
+    pub fn data_type(&self) -> NvType {
+        NvType::from_raw(self.raw_type())
+    }
 
-                NvData::NvListRef(l)
-            }
-            sys::data_type_t::DATA_TYPE_BYTE_ARRAY => {
-                let slice = unsafe {
-                    let mut array = MaybeUninit::uninit();
-                    let mut len = MaybeUninit::uninit();
-                    sys::nvpair_value_byte_array(
-                        self.as_ptr(),
-                        array.as_mut_ptr(),
-                        len.as_mut_ptr(),
-                    );
-                    std::slice::from_raw_parts(array.assume_init(), len.assume_init() as usize)
-                };
-
-                NvData::ByteArray(slice)
-            }
-            sys::data_type_t::DATA_TYPE_INT8_ARRAY => {
-                let slice = unsafe {
-                    let mut array = MaybeUninit::uninit();
-                    let mut len = MaybeUninit::uninit();
-                    sys::nvpair_value_int8_array(
-                        self.as_ptr(),
-                        array.as_mut_ptr(),
-                        len.as_mut_ptr(),
-                    );
-                    std::slice::from_raw_parts(array.assume_init(), len.assume_init() as usize)
-                };
-
-                NvData::Int8Array(slice)
-            }
-            sys::data_type_t::DATA_TYPE_UINT8_ARRAY => {
-                let slice = unsafe {
-                    let mut array = MaybeUninit::uninit();
-                    let mut len = MaybeUninit::uninit();
-                    sys::nvpair_value_uint8_array(
-                        self.as_ptr(),
-                        array.as_mut_ptr(),
-                        len.as_mut_ptr(),
-                    );
-                    std::slice::from_raw_parts(array.assume_init(), len.assume_init() as usize)
-                };
-
-                NvData::Uint8Array(slice)
-            }
-            sys::data_type_t::DATA_TYPE_INT16_ARRAY => {
-                let slice = unsafe {
-                    let mut array = MaybeUninit::uninit();
-                    let mut len = MaybeUninit::uninit();
-                    sys::nvpair_value_int16_array(
-                        self.as_ptr(),
-                        array.as_mut_ptr(),
-                        len.as_mut_ptr(),
-                    );
-                    std::slice::from_raw_parts(array.assume_init(), len.assume_init() as usize)
-                };
-
-                NvData::Int16Array(slice)
-            }
-            sys::data_type_t::DATA_TYPE_UINT16_ARRAY => {
-                let slice = unsafe {
-                    let mut array = MaybeUninit::uninit();
-                    let mut len = MaybeUninit::uninit();
-                    sys::nvpair_value_uint16_array(
-                        self.as_ptr(),
-                        array.as_mut_ptr(),
-                        len.as_mut_ptr(),
-                    );
-                    std::slice::from_raw_parts(array.assume_init(), len.assume_init() as usize)
-                };
-
-                NvData::Uint16Array(slice)
-            }
-            sys::data_type_t::DATA_TYPE_INT32_ARRAY => {
-                let slice = unsafe {
-                    let mut array = MaybeUninit::uninit();
-                    let mut len = MaybeUninit::uninit();
-                    sys::nvpair_value_int32_array(
-                        self.as_ptr(),
-                        array.as_mut_ptr(),
-                        len.as_mut_ptr(),
-                    );
-                    std::slice::from_raw_parts(array.assume_init(), len.assume_init() as usize)
-                };
-
-                NvData::Int32Array(slice)
-            }
-            sys::data_type_t::DATA_TYPE_UINT32_ARRAY => {
-                let slice = unsafe {
-                    let mut array = MaybeUninit::uninit();
-                    let mut len = MaybeUninit::uninit();
-                    sys::nvpair_value_uint32_array(
-                        self.as_ptr(),
-                        array.as_mut_ptr(),
-                        len.as_mut_ptr(),
-                    );
-                    std::slice::from_raw_parts(array.assume_init(), len.assume_init() as usize)
-                };
-
-                NvData::Uint32Array(slice)
-            }
-            sys::data_type_t::DATA_TYPE_INT64_ARRAY => {
-                let slice = unsafe {
-                    let mut array = MaybeUninit::uninit();
-                    let mut len = MaybeUninit::uninit();
-                    sys::nvpair_value_int64_array(
-                        self.as_ptr(),
-                        array.as_mut_ptr(),
-                        len.as_mut_ptr(),
-                    );
-                    std::slice::from_raw_parts(array.assume_init(), len.assume_init() as usize)
-                };
-
-                NvData::Int64Array(slice)
-            }
-            sys::data_type_t::DATA_TYPE_UINT64_ARRAY => {
-                let slice = unsafe {
-                    let mut array = MaybeUninit::uninit();
-                    let mut len = MaybeUninit::uninit();
-                    sys::nvpair_value_uint64_array(
-                        self.as_ptr(),
-                        array.as_mut_ptr(),
-                        len.as_mut_ptr(),
-                    );
-                    std::slice::from_raw_parts(array.assume_init(), len.assume_init() as usize)
-                };
-
-                NvData::Uint64Array(slice)
-            }
-            sys::data_type_t::DATA_TYPE_NVLIST_ARRAY => {
-                let slice = unsafe {
-                    let mut array = MaybeUninit::uninit();
-                    let mut len = MaybeUninit::uninit();
-                    sys::nvpair_value_nvlist_array(
-                        self.as_ptr(),
-                        array.as_mut_ptr(),
-                        len.as_mut_ptr(),
-                    );
-                    std::slice::from_raw_parts(array.assume_init(), len.assume_init() as usize)
-                };
-                let mut vec = Vec::with_capacity(slice.len());
-                for p in slice {
-                    vec.push(unsafe { NvListRef::from_ptr(*p) });
-                }
+    fn raw_type(&self) -> sys::data_type_t::Type {
+        unsafe { sys::nvpair_type(self.as_ptr()) }
+    }
 
-                NvData::NvListRefArray(vec)
-            }
-            /* TODO:
-            pub const DATA_TYPE_STRING_ARRAY: Type = 17;
-            pub const DATA_TYPE_HRTIME: Type = 18;
-            pub const DATA_TYPE_BOOLEAN_ARRAY: Type = 24;
-            pub const DATA_TYPE_DOUBLE: Type = 27;
-            */
+    /// Decode this pair's data, whatever its type
+    ///
+    /// When only one type of data is expected, the typed getters ([`as_u64()`](Self::as_u64),
+    /// [`as_list()`](Self::as_list), etc.) are cheaper: they don't need to match on `NvData`, and
+    /// [`as_list_array()`](Self::as_list_array) doesn't copy the array.
+    pub fn data(&self) -> NvData<'_> {
+        // each getter only fails if the type doesn't match, which `data_type()` rules out
+        match self.data_type() {
+            NvType::Boolean => NvData::Bool,
+            NvType::BooleanValue => NvData::BoolV(self.as_bool().unwrap()),
+            NvType::Byte => NvData::Byte(self.as_byte().unwrap()),
+            NvType::Int8 => NvData::Int8(self.as_i8().unwrap()),
+            NvType::Uint8 => NvData::Uint8(self.as_u8().unwrap()),
+            NvType::Int16 => NvData::Int16(self.as_i16().unwrap()),
+            NvType::Uint16 => NvData::Uint16(self.as_u16().unwrap()),
+            NvType::Int32 => NvData::Int32(self.as_i32().unwrap()),
+            NvType::Uint32 => NvData::Uint32(self.as_u32().unwrap()),
+            NvType::Int64 => NvData::Int64(self.as_i64().unwrap()),
+            NvType::Uint64 => NvData::Uint64(self.as_u64().unwrap()),
+            NvType::String => NvData::Str(self.as_str().unwrap()),
+            NvType::NvList => NvData::NvListRef(self.as_list().unwrap()),
+            NvType::ByteArray => NvData::ByteArray(self.as_byte_array().unwrap()),
+            NvType::Int8Array => NvData::Int8Array(self.as_i8_array().unwrap()),
+            NvType::Uint8Array => NvData::Uint8Array(self.as_u8_array().unwrap()),
+            NvType::Int16Array => NvData::Int16Array(self.as_i16_array().unwrap()),
+            NvType::Uint16Array => NvData::Uint16Array(self.as_u16_array().unwrap()),
+            NvType::Int32Array => NvData::Int32Array(self.as_i32_array().unwrap()),
+            NvType::Uint32Array => NvData::Uint32Array(self.as_u32_array().unwrap()),
+            NvType::Int64Array => NvData::Int64Array(self.as_i64_array().unwrap()),
+            NvType::Uint64Array => NvData::Uint64Array(self.as_u64_array().unwrap()),
+            NvType::NvListArray => NvData::NvListRefArray(self.as_list_array().unwrap().to_vec()),
             _ => NvData::Unknown,
         }
     }
 
+    nvpair_value!(
+        /// The value of a `boolean_value` pair
+        as_bool,
+        bool,
+        DATA_TYPE_BOOLEAN_VALUE,
+        |p| {
+            let mut v = MaybeUninit::uninit();
+            sys::nvpair_value_boolean_value(p, v.as_mut_ptr());
+            v.assume_init() == sys::boolean_t::B_TRUE
+        }
+    );
+    nvpair_value!(
+        /// The value of a `byte` pair
+        as_byte,
+        u8,
+        DATA_TYPE_BYTE,
+        nvpair_value_byte
+    );
+    nvpair_value!(
+        /// The value of a `int8` pair
+        as_i8,
+        i8,
+        DATA_TYPE_INT8,
+        nvpair_value_int8
+    );
+    nvpair_value!(
+        /// The value of a `uint8` pair
+        as_u8,
+        u8,
+        DATA_TYPE_UINT8,
+        nvpair_value_uint8
+    );
+    nvpair_value!(
+        /// The value of a `int16` pair
+        as_i16,
+        i16,
+        DATA_TYPE_INT16,
+        nvpair_value_int16
+    );
+    nvpair_value!(
+        /// The value of a `uint16` pair
+        as_u16,
+        u16,
+        DATA_TYPE_UINT16,
+        nvpair_value_uint16
+    );
+    nvpair_value!(
+        /// The value of a `int32` pair
+        as_i32,
+        i32,
+        DATA_TYPE_INT32,
+        nvpair_value_int32
+    );
+    nvpair_value!(
+        /// The value of a `uint32` pair
+        as_u32,
+        u32,
+        DATA_TYPE_UINT32,
+        nvpair_value_uint32
+    );
+    nvpair_value!(
+        /// The value of a `int64` pair
+        as_i64,
+        i64,
+        DATA_TYPE_INT64,
+        nvpair_value_int64
+    );
+    nvpair_value!(
+        /// The value of a `uint64` pair
+        as_u64,
+        u64,
+        DATA_TYPE_UINT64,
+        nvpair_value_uint64
+    );
+    nvpair_value!(
+        /// The value of a `string` pair
+        as_str,
+        &ffi::CStr,
+        DATA_TYPE_STRING,
+        |p| {
+            let mut v = MaybeUninit::uninit();
+            sys::nvpair_value_string(p, v.as_mut_ptr());
+            ffi::CStr::from_ptr(v.assume_init())
+        }
+    );
+    nvpair_value!(
+        /// The list held by an `nvlist` pair
+        as_list,
+        &NvListRef,
+        DATA_TYPE_NVLIST,
+        |p| {
+            let mut v = MaybeUninit::uninit();
+            sys::nvpair_value_nvlist(p, v.as_mut_ptr());
+            NvListRef::from_ptr(v.assume_init())
+        }
+    );
+
+    nvpair_array!(
+        /// The elements of a `byte_array` pair
+        as_byte_array,
+        u8,
+        DATA_TYPE_BYTE_ARRAY,
+        nvpair_value_byte_array
+    );
+    nvpair_array!(
+        /// The elements of a `int8_array` pair
+        as_i8_array,
+        i8,
+        DATA_TYPE_INT8_ARRAY,
+        nvpair_value_int8_array
+    );
+    nvpair_array!(
+        /// The elements of a `uint8_array` pair
+        as_u8_array,
+        u8,
+        DATA_TYPE_UINT8_ARRAY,
+        nvpair_value_uint8_array
+    );
+    nvpair_array!(
+        /// The elements of a `int16_array` pair
+        as_i16_array,
+        i16,
+        DATA_TYPE_INT16_ARRAY,
+        nvpair_value_int16_array
+    );
+    nvpair_array!(
+        /// The elements of a `uint16_array` pair
+        as_u16_array,
+        u16,
+        DATA_TYPE_UINT16_ARRAY,
+        nvpair_value_uint16_array
+    );
+    nvpair_array!(
+        /// The elements of a `int32_array` pair
+        as_i32_array,
+        i32,
+        DATA_TYPE_INT32_ARRAY,
+        nvpair_value_int32_array
+    );
+    nvpair_array!(
+        /// The elements of a `uint32_array` pair
+        as_u32_array,
+        u32,
+        DATA_TYPE_UINT32_ARRAY,
+        nvpair_value_uint32_array
+    );
+    nvpair_array!(
+        /// The elements of a `int64_array` pair
+        as_i64_array,
+        i64,
+        DATA_TYPE_INT64_ARRAY,
+        nvpair_value_int64_array
+    );
+    nvpair_array!(
+        /// The elements of a `uint64_array` pair
+        as_u64_array,
+        u64,
+        DATA_TYPE_UINT64_ARRAY,
+        nvpair_value_uint64_array
+    );
+
+    /// The lists of an `nvlist_array` pair, without copying the array
+    pub fn as_list_array(&self) -> Option<&[&NvListRef]> {
+        if self.raw_type() != sys::data_type_t::DATA_TYPE_NVLIST_ARRAY {
+            return None;
+        }
+        Some(unsafe {
+            let mut array = MaybeUninit::uninit();
+            let mut len = MaybeUninit::uninit();
+            sys::nvpair_value_nvlist_array(self.as_ptr(), array.as_mut_ptr(), len.as_mut_ptr());
+            // `&NvListRef` is a non-null `*mut nvlist_t`, and the elements are never null
+            let array: *mut *mut sys::nvlist = array.assume_init();
+            let len = len.assume_init() as usize;
+            if len == 0 {
+                &[]
+            } else {
+                std::slice::from_raw_parts(array as *const &NvListRef, len)
+            }
+        })
+    }
+
     pub fn tuple(&self) -> (&ffi::CStr, NvData<'_>) {
         (self.name(), self.data())
     }
@@ -1110,4 +1739,35 @@ impl<'a> NvData<'a> {
             _ => None,
         }
     }
+
+    /// The name of this data's type, as used by libnvpair (`data_type_t`) without the
+    /// `DATA_TYPE_` prefix: `"uint64"`, `"nvlist_array"`, etc.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            NvData::Unknown => "unknown",
+            NvData::Bool => "boolean",
+            NvData::BoolV(_) => "boolean_value",
+            NvData::Byte(_) => "byte",
+            NvData::Int8(_) => "int8",
+            NvData::Uint8(_) => "uint8",
+            NvData::Int16(_) => "int16",
+            NvData::Uint16(_) => "uint16",
+            NvData::Int32(_) => "int32",
+            NvData::Uint32(_) => "uint32",
+            NvData::Int64(_) => "int64",
+            NvData::Uint64(_) => "uint64",
+            NvData::Str(_) => "string",
+            NvData::NvListRef(_) => "nvlist",
+            NvData::ByteArray(_) => "byte_array",
+            NvData::Int8Array(_) => "int8_array",
+            NvData::Uint8Array(_) => "uint8_array",
+            NvData::Int16Array(_) => "int16_array",
+            NvData::Uint16Array(_) => "uint16_array",
+            NvData::Int32Array(_) => "int32_array",
+            NvData::Uint32Array(_) => "uint32_array",
+            NvData::Int64Array(_) => "int64_array",
+            NvData::Uint64Array(_) => "uint64_array",
+            NvData::NvListRefArray(_) => "nvlist_array",
+        }
+    }
 }
@@ -0,0 +1,665 @@
+//! Framing and validation of packed nvlists
+//!
+//! A packed nvlist doesn't record its own length. It starts with a 4 byte header (encoding,
+//! endianness, 2 reserved bytes), followed by the list itself:
+//!
+//!  - native encoding: an 8 byte list header, then each pair as a copy of its `nvpair_t` (which
+//!    starts with its size as an `i32`), then 4 zero bytes. The pairs of nested nvlists (and of
+//!    each element of nvlist arrays) are encoded the same way, immediately after the pair that
+//!    holds them.
+//!  - xdr encoding: an 8 byte list header, then each pair prefixed with its encoded and decoded
+//!    sizes (big endian `i32`s, the encoded size covering any nested nvlists), then 8 zero bytes.
+//!
+//! To find the end of a packed list in a stream, we walk these size fields. To check a list from an
+//! untrusted source before handing it to `nvlist_unpack()`, we also check each pair's name, type
+//! and value against its size.
+use crate::{sys, NvError, NvResult};
+use std::convert::TryInto;
+use std::fmt;
+use std::io::{self, Read};
+
+const NVS_HEADER_LEN: usize = 4;
+const NVL_HEADER_LEN: usize = 8;
+const NVP_HEADER_LEN: usize = 16;
+
+const ENCODE_NATIVE: u8 = sys::NV_ENCODE_NATIVE as u8;
+const ENCODE_XDR: u8 = sys::NV_ENCODE_XDR as u8;
+
+fn corrupt(offset: usize, reason: &'static str) -> UnpackError {
+    UnpackError::Corrupt { offset, reason }
+}
+
+/// Reads exactly one packed nvlist from `r`, keeping the bytes
+struct Framer<'a, R: ?Sized> {
+    r: &'a mut R,
+    buf: Vec<u8>,
+    little_endian: bool,
+}
+
+impl<'a, R: Read + ?Sized> Framer<'a, R> {
+    /// Append the next `len` bytes to `buf`, returning the offset they start at
+    fn read(&mut self, len: usize) -> NvResult<usize> {
+        let start = self.buf.len();
+        // `take()` grows `buf` as data arrives, rather than trusting `len` up front
+        (&mut *self.r).take(len as u64).read_to_end(&mut self.buf)?;
+        if self.buf.len() - start != len {
+            return Err(NvError::Io(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("packed nvlist truncated at offset {}", self.buf.len()),
+            )));
+        }
+        Ok(start)
+    }
+
+    fn i32_at(&self, offset: usize) -> i32 {
+        let b: [u8; 4] = self.buf[offset..offset + 4].try_into().unwrap();
+        if self.little_endian {
+            i32::from_le_bytes(b)
+        } else {
+            i32::from_be_bytes(b)
+        }
+    }
+
+    /// Read a pair size field and check it covers at least `min` bytes. `None` marks the end of
+    /// a list.
+    fn pair_size(&mut self, field_len: usize, min: usize) -> NvResult<Option<usize>> {
+        let at = self.read(field_len)?;
+        let size = self.i32_at(at);
+        if size == 0 {
+            Ok(None)
+        } else if size < 0 || (size as usize) < min {
+            Err(corrupt(at, "invalid pair size").into())
+        } else {
+            Ok(Some(size as usize))
+        }
+    }
+
+    fn native(&mut self) -> NvResult<()> {
+        // for each list being read, the number of nested lists that follow its current pair
+        let mut open = vec![0u32];
+        self.read(NVL_HEADER_LEN)?;
+
+        while let Some(nested) = open.last_mut() {
+            if *nested > 0 {
+                *nested -= 1;
+                self.read(NVL_HEADER_LEN)?;
+                open.push(0);
+                continue;
+            }
+
+            let size = match self.pair_size(4, NVP_HEADER_LEN)? {
+                Some(size) => size,
+                None => {
+                    open.pop();
+                    continue;
+                }
+            };
+            let pair = self.read(size - 4)? - 4;
+            let nelem = self.i32_at(pair + 8);
+            *nested = match self.i32_at(pair + 12) {
+                sys::data_type_t::DATA_TYPE_NVLIST => 1,
+                sys::data_type_t::DATA_TYPE_NVLIST_ARRAY if nelem >= 0 => nelem as u32,
+                sys::data_type_t::DATA_TYPE_NVLIST_ARRAY => {
+                    return Err(corrupt(pair + 8, "invalid element count").into())
+                }
+                _ => 0,
+            };
+        }
+        Ok(())
+    }
+
+    fn xdr(&mut self) -> NvResult<()> {
+        self.read(NVL_HEADER_LEN)?;
+        // each pair: encoded size, decoded size, then the rest of the pair
+        while let Some(size) = self.pair_size(4, 8)? {
+            self.read(size - 4)?;
+        }
+        // the decoded size of the terminator
+        self.read(4)?;
+        Ok(())
+    }
+}
+
+/// Read the bytes of a single packed nvlist from `r`, leaving `r` positioned just after it
+pub(crate) fn read_packed<R: Read + ?Sized>(r: &mut R) -> NvResult<Vec<u8>> {
+    let mut f = Framer {
+        r,
+        buf: Vec::new(),
+        little_endian: false,
+    };
+    f.read(NVS_HEADER_LEN)?;
+    f.little_endian = f.buf[1] != 0;
+    match f.buf[0] {
+        ENCODE_NATIVE => f.native()?,
+        ENCODE_XDR => {
+            // xdr is always big endian, whatever the header says
+            f.little_endian = false;
+            f.xdr()?
+        }
+        _ => return Err(corrupt(0, "unknown encoding").into()),
+    }
+    Ok(f.buf)
+}
+
+/// Limits applied by [`NvList::try_unpack_limited()`](crate::NvList::try_unpack_limited)
+#[derive(Debug, Clone, Copy)]
+pub struct UnpackLimits {
+    max_depth: usize,
+    max_pairs: usize,
+    max_size: usize,
+}
+
+impl Default for UnpackLimits {
+    fn default() -> Self {
+        UnpackLimits {
+            max_depth: 32,
+            max_pairs: 1 << 16,
+            max_size: 16 << 20,
+        }
+    }
+}
+
+impl UnpackLimits {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Maximum nesting of nvlists. The top level list has a depth of 0.
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = depth;
+        self
+    }
+
+    /// Maximum number of pairs, counting those in nested nvlists
+    pub fn max_pairs(mut self, pairs: usize) -> Self {
+        self.max_pairs = pairs;
+        self
+    }
+
+    /// Maximum size of the packed buffer, and (approximately) of the memory libnvpair will
+    /// allocate to unpack it
+    pub fn max_size(mut self, size: usize) -> Self {
+        self.max_size = size;
+        self
+    }
+}
+
+/// Why a packed nvlist was rejected by [`NvList::try_unpack_limited()`](crate::NvList::try_unpack_limited)
+///
+/// Offsets are in bytes from the start of the buffer.
+#[derive(Debug)]
+pub enum UnpackError {
+    /// The buffer ends before the nvlist does
+    Truncated { offset: usize },
+    /// The data at `offset` isn't a valid part of a packed nvlist
+    Corrupt { offset: usize, reason: &'static str },
+    /// Unpacking would exceed one of the [`UnpackLimits`]
+    Limit { offset: usize, limit: &'static str },
+    /// libnvpair failed to unpack the (validated) buffer
+    Unpack(NvError),
+}
+
+impl UnpackError {
+    pub fn offset(&self) -> Option<usize> {
+        match *self {
+            UnpackError::Truncated { offset }
+            | UnpackError::Corrupt { offset, .. }
+            | UnpackError::Limit { offset, .. } => Some(offset),
+            UnpackError::Unpack(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for UnpackError {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UnpackError::Truncated { offset } => {
+                write!(fmt, "packed nvlist truncated at offset {}", offset)
+            }
+            UnpackError::Corrupt { offset, reason } => {
+                write!(fmt, "packed nvlist: {} at offset {}", reason, offset)
+            }
+            UnpackError::Limit { offset, limit } => {
+                write!(
+                    fmt,
+                    "packed nvlist exceeds {} limit at offset {}",
+                    limit, offset
+                )
+            }
+            UnpackError::Unpack(e) => write!(fmt, "nvlist_unpack failed: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for UnpackError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            UnpackError::Unpack(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<UnpackError> for NvError {
+    fn from(e: UnpackError) -> Self {
+        match e {
+            UnpackError::Truncated { offset } | UnpackError::Corrupt { offset, .. } => {
+                NvError::Corrupt {
+                    offset: Some(offset),
+                }
+            }
+            UnpackError::Limit { .. } => NvError::Io(io::Error::new(io::ErrorKind::InvalidData, e)),
+            UnpackError::Unpack(e) => e,
+        }
+    }
+}
+
+impl From<UnpackError> for io::Error {
+    fn from(e: UnpackError) -> Self {
+        match e {
+            UnpackError::Unpack(e) => e.into(),
+            UnpackError::Truncated { .. } => io::Error::new(io::ErrorKind::UnexpectedEof, e),
+            _ => io::Error::new(io::ErrorKind::InvalidData, e),
+        }
+    }
+}
+
+// rough cost of each list and pair beyond their packed size (`nvlist_t` + `nvpriv_t`, `i_nvp_t`)
+const LIST_OVERHEAD: usize = 64;
+const PAIR_OVERHEAD: usize = 16;
+
+const NVLIST_T_LEN: usize = 24;
+
+fn align(v: usize, to: usize) -> usize {
+    (v + to - 1) & !(to - 1)
+}
+
+/// Checks a packed nvlist without allocating anything, following the same rules as libnvpair
+struct Validator<'a> {
+    buf: &'a [u8],
+    pos: usize,
+    little_endian: bool,
+    limits: &'a UnpackLimits,
+    pairs: usize,
+    size: usize,
+}
+
+impl<'a> Validator<'a> {
+    /// Consume `len` bytes, returning where they start
+    fn take(&mut self, len: usize) -> Result<usize, UnpackError> {
+        let start = self.pos;
+        match start.checked_add(len) {
+            Some(end) if end <= self.buf.len() => {
+                self.pos = end;
+                Ok(start)
+            }
+            _ => Err(UnpackError::Truncated {
+                offset: self.buf.len(),
+            }),
+        }
+    }
+
+    fn i32_at(&self, offset: usize) -> i32 {
+        let b: [u8; 4] = self.buf[offset..offset + 4].try_into().unwrap();
+        if self.little_endian {
+            i32::from_le_bytes(b)
+        } else {
+            i32::from_be_bytes(b)
+        }
+    }
+
+    fn u32(&mut self) -> Result<u32, UnpackError> {
+        let at = self.take(4)?;
+        Ok(self.i32_at(at) as u32)
+    }
+
+    fn count(&mut self, offset: usize, size: usize) -> Result<(), UnpackError> {
+        self.pairs += 1;
+        if self.pairs > self.limits.max_pairs {
+            return Err(UnpackError::Limit {
+                offset,
+                limit: "pair count",
+            });
+        }
+        self.size = self.size.saturating_add(size);
+        if self.size > self.limits.max_size {
+            return Err(UnpackError::Limit {
+                offset,
+                limit: "size",
+            });
+        }
+        Ok(())
+    }
+
+    fn list_header(&mut self, depth: usize) -> Result<(), UnpackError> {
+        let at = self.take(NVL_HEADER_LEN)?;
+        if depth > self.limits.max_depth {
+            return Err(UnpackError::Limit {
+                offset: at,
+                limit: "depth",
+            });
+        }
+        if self.i32_at(at) != sys::NV_VERSION {
+            return Err(corrupt(at, "unsupported nvlist version"));
+        }
+        self.size = self.size.saturating_add(LIST_OVERHEAD);
+        Ok(())
+    }
+
+    fn nelem(&self, at: usize, ty: i32) -> Result<usize, UnpackError> {
+        use sys::data_type_t::*;
+
+        let nelem = self.i32_at(at);
+        let ok = match ty {
+            DATA_TYPE_BOOLEAN => nelem == 0,
+            DATA_TYPE_BOOLEAN_VALUE
+            | DATA_TYPE_BYTE
+            | DATA_TYPE_INT8
+            | DATA_TYPE_UINT8
+            | DATA_TYPE_INT16
+            | DATA_TYPE_UINT16
+            | DATA_TYPE_INT32
+            | DATA_TYPE_UINT32
+            | DATA_TYPE_INT64
+            | DATA_TYPE_UINT64
+            | DATA_TYPE_HRTIME
+            | DATA_TYPE_DOUBLE
+            | DATA_TYPE_STRING
+            | DATA_TYPE_NVLIST => nelem == 1,
+            DATA_TYPE_BYTE_ARRAY
+            | DATA_TYPE_INT8_ARRAY
+            | DATA_TYPE_UINT8_ARRAY
+            | DATA_TYPE_INT16_ARRAY
+            | DATA_TYPE_UINT16_ARRAY
+            | DATA_TYPE_INT32_ARRAY
+            | DATA_TYPE_UINT32_ARRAY
+            | DATA_TYPE_INT64_ARRAY
+            | DATA_TYPE_UINT64_ARRAY
+            | DATA_TYPE_BOOLEAN_ARRAY
+            | DATA_TYPE_STRING_ARRAY
+            | DATA_TYPE_NVLIST_ARRAY => nelem >= 0,
+            _ => return Err(corrupt(at + 4, "unknown data type")),
+        };
+        if ok {
+            Ok(nelem as usize)
+        } else {
+            Err(corrupt(at, "invalid element count"))
+        }
+    }
+
+    /// `n` NUL terminated strings packed together in `buf[start..end]`. Returns their total
+    /// length.
+    fn c_strings(&self, start: usize, end: usize, n: usize) -> Result<usize, UnpackError> {
+        let mut pos = start;
+        for _ in 0..n {
+            match self.buf[pos..end].iter().position(|&b| b == 0) {
+                Some(l) => pos += l + 1,
+                None => return Err(corrupt(pos, "string is not NUL terminated")),
+            }
+        }
+        Ok(pos - start)
+    }
+
+    fn native_list(&mut self, depth: usize) -> Result<(), UnpackError> {
+        use sys::data_type_t::*;
+
+        self.list_header(depth)?;
+        loop {
+            let pair = self.take(4)?;
+            let size = self.i32_at(pair);
+            if size == 0 {
+                return Ok(());
+            }
+            if size < NVP_HEADER_LEN as i32 {
+                return Err(corrupt(pair, "invalid pair size"));
+            }
+            let size = size as usize;
+            self.take(size - 4)?;
+            let end = pair + size;
+            self.count(pair, size + PAIR_OVERHEAD)?;
+
+            let b = [self.buf[pair + 4], self.buf[pair + 5]];
+            let name_sz = if self.little_endian {
+                i16::from_le_bytes(b)
+            } else {
+                i16::from_be_bytes(b)
+            };
+            if name_sz <= 0 || NVP_HEADER_LEN + name_sz as usize > size {
+                return Err(corrupt(pair + 4, "invalid name size"));
+            }
+            let name_sz = name_sz as usize;
+            let name = &self.buf[pair + NVP_HEADER_LEN..pair + NVP_HEADER_LEN + name_sz];
+            if name.iter().position(|&b| b == 0) != Some(name_sz - 1) {
+                return Err(corrupt(pair + NVP_HEADER_LEN, "invalid name"));
+            }
+
+            let ty = self.i32_at(pair + 12);
+            let nelem = self.nelem(pair + 8, ty)?;
+            let value = pair + align(NVP_HEADER_LEN + name_sz, 8);
+            if value > end {
+                return Err(corrupt(pair, "pair too small for its name"));
+            }
+            let elem = match ty {
+                DATA_TYPE_BOOLEAN => 0,
+                DATA_TYPE_BYTE
+                | DATA_TYPE_INT8
+                | DATA_TYPE_UINT8
+                | DATA_TYPE_BYTE_ARRAY
+                | DATA_TYPE_INT8_ARRAY
+                | DATA_TYPE_UINT8_ARRAY => 1,
+                DATA_TYPE_INT16
+                | DATA_TYPE_UINT16
+                | DATA_TYPE_INT16_ARRAY
+                | DATA_TYPE_UINT16_ARRAY => 2,
+                DATA_TYPE_BOOLEAN_VALUE
+                | DATA_TYPE_INT32
+                | DATA_TYPE_UINT32
+                | DATA_TYPE_BOOLEAN_ARRAY
+                | DATA_TYPE_INT32_ARRAY
+                | DATA_TYPE_UINT32_ARRAY => 4,
+                DATA_TYPE_INT64
+                | DATA_TYPE_UINT64
+                | DATA_TYPE_HRTIME
+                | DATA_TYPE_DOUBLE
+                | DATA_TYPE_INT64_ARRAY
+                | DATA_TYPE_UINT64_ARRAY => 8,
+                // a pointer for each string, followed by the strings
+                DATA_TYPE_STRING_ARRAY => 8,
+                DATA_TYPE_STRING => 0,
+                DATA_TYPE_NVLIST => NVLIST_T_LEN,
+                // a pointer and an `nvlist_t` for each list
+                _ => 8 + NVLIST_T_LEN,
+            };
+            let fixed = elem
+                .checked_mul(nelem)
+                .filter(|&l| l <= end - value)
+                .ok_or_else(|| corrupt(pair, "pair too small for its value"))?;
+            match ty {
+                DATA_TYPE_STRING => {
+                    self.c_strings(value, end, 1)?;
+                }
+                DATA_TYPE_STRING_ARRAY => {
+                    self.c_strings(value + fixed, end, nelem)?;
+                }
+                _ => {}
+            }
+
+            // nested lists follow the pair
+            match ty {
+                DATA_TYPE_NVLIST => self.native_list(depth + 1)?,
+                DATA_TYPE_NVLIST_ARRAY => {
+                    for _ in 0..nelem {
+                        self.native_list(depth + 1)?;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// An xdr string: length, then the bytes padded to a multiple of 4
+    fn xdr_string(&mut self, end: usize) -> Result<(), UnpackError> {
+        let at = self.pos;
+        let len = self.u32()? as usize;
+        let s = self.take(align(len, 4))?;
+        if self.pos > end {
+            return Err(corrupt(at, "string overruns its pair"));
+        }
+        if self.buf[s..s + len].contains(&0) {
+            return Err(corrupt(s, "string contains a NUL"));
+        }
+        Ok(())
+    }
+
+    fn xdr_list(&mut self, depth: usize) -> Result<(), UnpackError> {
+        use sys::data_type_t::*;
+
+        self.list_header(depth)?;
+        loop {
+            let pair = self.pos;
+            let encode_size = self.u32()? as i32;
+            let decode_size = self.u32()? as i32;
+            if encode_size == 0 && decode_size == 0 {
+                return Ok(());
+            }
+            if encode_size < 8 || decode_size < NVP_HEADER_LEN as i32 {
+                return Err(corrupt(pair, "invalid pair size"));
+            }
+            let end = pair + encode_size as usize;
+            if end > self.buf.len() {
+                return Err(UnpackError::Truncated {
+                    offset: self.buf.len(),
+                });
+            }
+            self.count(pair, decode_size as usize + PAIR_OVERHEAD)?;
+
+            let name = self.pos;
+            self.xdr_string(end)?;
+            if self.i32_at(name) == 0 {
+                return Err(corrupt(name, "empty name"));
+            }
+            let at = self.take(8)?;
+            let ty = self.i32_at(at);
+            let nelem = self.nelem(at + 4, ty)?;
+
+            // chars and shorts are widened to 4 bytes
+            let elem: usize = match ty {
+                DATA_TYPE_BOOLEAN_VALUE
+                | DATA_TYPE_BYTE
+                | DATA_TYPE_INT8
+                | DATA_TYPE_UINT8
+                | DATA_TYPE_INT16
+                | DATA_TYPE_UINT16
+                | DATA_TYPE_INT32
+                | DATA_TYPE_UINT32
+                | DATA_TYPE_INT8_ARRAY
+                | DATA_TYPE_UINT8_ARRAY
+                | DATA_TYPE_INT16_ARRAY
+                | DATA_TYPE_UINT16_ARRAY
+                | DATA_TYPE_INT32_ARRAY
+                | DATA_TYPE_UINT32_ARRAY
+                | DATA_TYPE_BOOLEAN_ARRAY => 4,
+                DATA_TYPE_INT64
+                | DATA_TYPE_UINT64
+                | DATA_TYPE_HRTIME
+                | DATA_TYPE_DOUBLE
+                | DATA_TYPE_INT64_ARRAY
+                | DATA_TYPE_UINT64_ARRAY => 8,
+                _ => 0,
+            };
+            // arrays other than bytes are written by `xdr_array()`, with an element count first
+            let counted = matches!(
+                ty,
+                DATA_TYPE_INT8_ARRAY
+                    | DATA_TYPE_UINT8_ARRAY
+                    | DATA_TYPE_INT16_ARRAY
+                    | DATA_TYPE_UINT16_ARRAY
+                    | DATA_TYPE_INT32_ARRAY
+                    | DATA_TYPE_UINT32_ARRAY
+                    | DATA_TYPE_INT64_ARRAY
+                    | DATA_TYPE_UINT64_ARRAY
+                    | DATA_TYPE_BOOLEAN_ARRAY
+            );
+            match ty {
+                // `nvs_xdr_nvp_op()` writes no value at all for an empty array
+                _ if nelem == 0 => {}
+                // opaque bytes, padded to a multiple of 4
+                DATA_TYPE_BYTE_ARRAY => {
+                    self.take(align(nelem, 4))?;
+                }
+                _ if counted => {
+                    let count = self.pos;
+                    if self.u32()? as usize != nelem {
+                        return Err(corrupt(count, "array count doesn't match its pair"));
+                    }
+                    let len = elem
+                        .checked_mul(nelem)
+                        .ok_or_else(|| corrupt(at + 4, "invalid element count"))?;
+                    self.take(len)?;
+                }
+                DATA_TYPE_STRING => self.xdr_string(end)?,
+                DATA_TYPE_STRING_ARRAY => {
+                    for _ in 0..nelem {
+                        self.xdr_string(end)?;
+                    }
+                }
+                DATA_TYPE_NVLIST => self.xdr_list(depth + 1)?,
+                DATA_TYPE_NVLIST_ARRAY => {
+                    for _ in 0..nelem {
+                        self.xdr_list(depth + 1)?;
+                    }
+                }
+                _ => {
+                    self.take(elem)?;
+                }
+            }
+            // `nvs_xdr_nvp_size()` still counts the element count of an empty array
+            let slack = if counted && nelem == 0 { 4 } else { 0 };
+            if self.pos != end && self.pos + slack != end {
+                return Err(corrupt(pair, "pair size doesn't match its contents"));
+            }
+        }
+    }
+}
+
+/// Check that `buf` holds exactly one well formed packed nvlist within `limits`
+pub(crate) fn validate(buf: &[u8], limits: &UnpackLimits) -> Result<(), UnpackError> {
+    if buf.len() > limits.max_size {
+        return Err(UnpackError::Limit {
+            offset: limits.max_size,
+            limit: "size",
+        });
+    }
+    let mut v = Validator {
+        buf,
+        pos: 0,
+        little_endian: false,
+        limits,
+        pairs: 0,
+        size: buf.len(),
+    };
+    v.take(NVS_HEADER_LEN)?;
+    let little_endian = match buf[1] {
+        0 => false,
+        1 => true,
+        _ => return Err(corrupt(1, "invalid byte order")),
+    };
+    match buf[0] {
+        ENCODE_NATIVE => {
+            if little_endian != cfg!(target_endian = "little") {
+                return Err(corrupt(
+                    1,
+                    "native encoding from a host of different byte order",
+                ));
+            }
+            v.little_endian = little_endian;
+            v.native_list(0)?;
+        }
+        ENCODE_XDR => v.xdr_list(0)?,
+        _ => return Err(corrupt(0, "unknown encoding")),
+    }
+    if v.pos != buf.len() {
+        return Err(corrupt(v.pos, "trailing data after nvlist"));
+    }
+    Ok(())
+}
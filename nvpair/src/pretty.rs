@@ -0,0 +1,173 @@
+use crate::{NvData, NvList, NvListRef};
+use std::fmt;
+
+/// Human readable, indented formatting of an nvlist
+///
+/// The layout follows `zdb`'s `dump_nvlist()`: one pair per line, nested nvlists and nvlist array
+/// elements on their own lines below their name, indented by 4 more spaces.
+///
+/// ```text
+/// version: 5000
+/// name: 'tank'
+/// vdev_tree:
+///     type: 'root'
+///     children[0]:
+///         type: 'disk'
+///         path: '/dev/sda1'
+/// ```
+///
+/// Obtained from [`NvListRef::pretty()`]. `Display` for `NvListRef` uses the default options.
+#[derive(Debug, Clone, Copy)]
+pub struct Pretty<'a> {
+    nv: &'a NvListRef,
+    indent: usize,
+    max_depth: Option<usize>,
+    hex_bytes: bool,
+}
+
+impl<'a> Pretty<'a> {
+    /// Only descend into `depth` levels of nested nvlists. Deeper nvlists and nvlist arrays are
+    /// summarized on a single line.
+    ///
+    /// A depth of `0` prints only the pairs of the top level list.
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = Some(depth);
+        self
+    }
+
+    /// Print byte and `uint8` arrays as a single hex string (`0x0a0b...`) rather than a list of
+    /// decimal numbers
+    pub fn hex_bytes(mut self, hex: bool) -> Self {
+        self.hex_bytes = hex;
+        self
+    }
+
+    /// Start with `indent` spaces of indentation. Nested lists add 4 more.
+    pub fn indent(mut self, indent: usize) -> Self {
+        self.indent = indent;
+        self
+    }
+
+    fn nested(&self, nv: &'a NvListRef) -> Self {
+        Pretty {
+            nv,
+            indent: self.indent + 4,
+            max_depth: self.max_depth.map(|d| d - 1),
+            ..*self
+        }
+    }
+
+    fn can_descend(&self) -> bool {
+        self.max_depth != Some(0)
+    }
+
+    fn bytes(&self, f: &mut fmt::Formatter<'_>, v: &[u8]) -> fmt::Result {
+        if self.hex_bytes {
+            write!(f, "0x")?;
+            for b in v {
+                write!(f, "{:02x}", b)?;
+            }
+            Ok(())
+        } else {
+            array(f, v)
+        }
+    }
+}
+
+fn array<T: fmt::Display>(f: &mut fmt::Formatter<'_>, v: &[T]) -> fmt::Result {
+    write!(f, "[")?;
+    for (i, e) in v.iter().enumerate() {
+        if i != 0 {
+            write!(f, " ")?;
+        }
+        write!(f, "{}", e)?;
+    }
+    write!(f, "]")
+}
+
+impl<'a> fmt::Display for Pretty<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let indent = self.indent;
+        for pair in self.nv.iter() {
+            let name = pair.name().to_string_lossy();
+            let data = pair.data();
+
+            match data {
+                NvData::NvListRef(l) if self.can_descend() => {
+                    writeln!(f, "{:indent$}{}:", "", name, indent = indent)?;
+                    fmt::Display::fmt(&self.nested(l), f)?;
+                    continue;
+                }
+                NvData::NvListRefArray(ref ls) if self.can_descend() => {
+                    for (i, l) in ls.iter().enumerate() {
+                        writeln!(f, "{:indent$}{}[{}]:", "", name, i, indent = indent)?;
+                        fmt::Display::fmt(&self.nested(l), f)?;
+                    }
+                    continue;
+                }
+                NvData::Bool => {
+                    writeln!(f, "{:indent$}{}", "", name, indent = indent)?;
+                    continue;
+                }
+                _ => {}
+            }
+
+            write!(f, "{:indent$}{}: ", "", name, indent = indent)?;
+            match data {
+                NvData::Unknown => write!(f, "<unknown type>")?,
+                NvData::Bool => unreachable!(),
+                NvData::BoolV(v) => write!(f, "{}", v)?,
+                NvData::Byte(v) => write!(f, "0x{:02x}", v)?,
+                NvData::Int8(v) => write!(f, "{}", v)?,
+                NvData::Uint8(v) => write!(f, "{}", v)?,
+                NvData::Int16(v) => write!(f, "{}", v)?,
+                NvData::Uint16(v) => write!(f, "{}", v)?,
+                NvData::Int32(v) => write!(f, "{}", v)?,
+                NvData::Uint32(v) => write!(f, "{}", v)?,
+                NvData::Int64(v) => write!(f, "{}", v)?,
+                NvData::Uint64(v) => write!(f, "{}", v)?,
+                NvData::Str(v) => write!(f, "'{}'", v.to_string_lossy())?,
+                NvData::NvListRef(l) => write!(f, "<nvlist, {} pairs>", l.iter().count())?,
+                NvData::ByteArray(v) => self.bytes(f, v)?,
+                NvData::Int8Array(v) => array(f, v)?,
+                NvData::Uint8Array(v) => self.bytes(f, v)?,
+                NvData::Int16Array(v) => array(f, v)?,
+                NvData::Uint16Array(v) => array(f, v)?,
+                NvData::Int32Array(v) => array(f, v)?,
+                NvData::Uint32Array(v) => array(f, v)?,
+                NvData::Int64Array(v) => array(f, v)?,
+                NvData::Uint64Array(v) => array(f, v)?,
+                NvData::NvListRefArray(v) => write!(f, "<{} nvlists>", v.len())?,
+            }
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl NvListRef {
+    /// Format this list for humans, in the style of `zdb`'s `dump_nvlist()`
+    ///
+    /// See [`Pretty`] for the available options.
+    pub fn pretty(&self) -> Pretty<'_> {
+        Pretty {
+            nv: self,
+            indent: 0,
+            max_depth: None,
+            hex_bytes: false,
+        }
+    }
+}
+
+impl fmt::Display for NvListRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.pretty(), f)
+    }
+}
+
+impl fmt::Display for NvList {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.pretty(), f)
+    }
+}
@@ -0,0 +1,237 @@
+use crate::{sys, NvData, NvDecode, NvEncode, NvError, NvList, NvListRef, NvPair, NvResult};
+use cstr_argument::CStrArgument;
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::{ffi, io};
+
+/// An owned copy of nvpair data, independent of any `nvlist_t`
+///
+/// Unlike [`NvData`], which borrows from the `NvList` it was read from, an `NvValue` may be kept
+/// around, compared, hashed and sent between threads freely.
+///
+/// Nested nvlists are kept as a list of `(name, value)` pairs in their original order, as nvlists
+/// without the `NV_UNIQUE_NAME` constraint may contain the same name more than once. Comparisons
+/// are therefore order sensitive. When converted back into an `NvList`, lists get the
+/// `NV_UNIQUE_NAME` constraint unless they contain duplicate names.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum NvValue {
+    Bool,
+    BoolV(bool),
+    Byte(u8),
+    Int8(i8),
+    Uint8(u8),
+    Int16(i16),
+    Uint16(u16),
+    Int32(i32),
+    Uint32(u32),
+    Int64(i64),
+    Uint64(u64),
+    Str(ffi::CString),
+    NvList(Vec<(ffi::CString, NvValue)>),
+    ByteArray(Vec<u8>),
+    Int8Array(Vec<i8>),
+    Uint8Array(Vec<u8>),
+    Int16Array(Vec<i16>),
+    Uint16Array(Vec<u16>),
+    Int32Array(Vec<i32>),
+    Uint32Array(Vec<u32>),
+    Int64Array(Vec<i64>),
+    Uint64Array(Vec<u64>),
+    NvListArray(Vec<Vec<(ffi::CString, NvValue)>>),
+}
+
+impl NvValue {
+    /// If this is an `NvList`, return the value of the first pair named `name`
+    pub fn get(&self, name: &str) -> Option<&NvValue> {
+        match self {
+            NvValue::NvList(pairs) => pairs
+                .iter()
+                .find(|(n, _)| n.as_bytes() == name.as_bytes())
+                .map(|(_, v)| v),
+            _ => None,
+        }
+    }
+}
+
+pub(crate) fn pairs_of(nv: &NvListRef) -> NvResult<Vec<(ffi::CString, NvValue)>> {
+    nv.iter()
+        .map(|pair| Ok((pair.name().to_owned(), pair.data().to_value()?)))
+        .collect()
+}
+
+fn list_from_pairs(pairs: &[(ffi::CString, NvValue)]) -> NvResult<NvList> {
+    // the flags of the original list aren't kept. Use `NV_UNIQUE_NAME` (as nearly all lists from
+    // zfs do) unless that would lose some of the pairs.
+    let names: std::collections::HashSet<_> = pairs.iter().map(|(n, _)| n).collect();
+    let mut nv = if names.len() == pairs.len() {
+        NvList::try_new_unique_names()?
+    } else {
+        NvList::try_new()?
+    };
+    for (name, value) in pairs {
+        value.insert_into(name.as_c_str(), &mut nv)?;
+    }
+    Ok(nv)
+}
+
+impl<'a> NvData<'a> {
+    /// Copy this data out of the `NvList` that holds it
+    ///
+    /// Returns an error for data types which `NvData` can't represent (`NvData::Unknown`).
+    pub fn to_value(&self) -> NvResult<NvValue> {
+        Ok(match self {
+            NvData::Unknown => {
+                return Err(NvError::Io(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "unsupported nvpair data type",
+                )))
+            }
+            NvData::Bool => NvValue::Bool,
+            NvData::BoolV(v) => NvValue::BoolV(*v),
+            NvData::Byte(v) => NvValue::Byte(*v),
+            NvData::Int8(v) => NvValue::Int8(*v),
+            NvData::Uint8(v) => NvValue::Uint8(*v),
+            NvData::Int16(v) => NvValue::Int16(*v),
+            NvData::Uint16(v) => NvValue::Uint16(*v),
+            NvData::Int32(v) => NvValue::Int32(*v),
+            NvData::Uint32(v) => NvValue::Uint32(*v),
+            NvData::Int64(v) => NvValue::Int64(*v),
+            NvData::Uint64(v) => NvValue::Uint64(*v),
+            NvData::Str(v) => NvValue::Str((*v).to_owned()),
+            NvData::NvListRef(v) => NvValue::NvList(pairs_of(v)?),
+            NvData::ByteArray(v) => NvValue::ByteArray(v.to_vec()),
+            NvData::Int8Array(v) => NvValue::Int8Array(v.to_vec()),
+            NvData::Uint8Array(v) => NvValue::Uint8Array(v.to_vec()),
+            NvData::Int16Array(v) => NvValue::Int16Array(v.to_vec()),
+            NvData::Uint16Array(v) => NvValue::Uint16Array(v.to_vec()),
+            NvData::Int32Array(v) => NvValue::Int32Array(v.to_vec()),
+            NvData::Uint32Array(v) => NvValue::Uint32Array(v.to_vec()),
+            NvData::Int64Array(v) => NvValue::Int64Array(v.to_vec()),
+            NvData::Uint64Array(v) => NvValue::Uint64Array(v.to_vec()),
+            NvData::NvListRefArray(v) => {
+                NvValue::NvListArray(v.iter().map(|l| pairs_of(l)).collect::<NvResult<_>>()?)
+            }
+        })
+    }
+}
+
+impl NvListRef {
+    /// Copy the entire list (recursively) into an owned [`NvValue::NvList`]
+    pub fn to_value(&self) -> NvResult<NvValue> {
+        Ok(NvValue::NvList(pairs_of(self)?))
+    }
+}
+
+impl TryFrom<&NvValue> for NvList {
+    type Error = NvError;
+
+    /// Build a new `NvList` from an [`NvValue::NvList`]. Other variants are rejected with an
+    /// `InvalidInput` [`NvError::Io`].
+    fn try_from(value: &NvValue) -> NvResult<Self> {
+        match value {
+            NvValue::NvList(pairs) => list_from_pairs(pairs),
+            _ => Err(NvError::invalid_input(
+                "only NvValue::NvList can be converted to an NvList",
+            )),
+        }
+    }
+}
+
+/// Decode every pair of `nv`, keyed by name
+///
+/// Names must be valid UTF-8. If a name appears more than once, the last pair wins.
+fn decode_map<V: NvDecode, M: Extend<(String, V)>>(nv: &NvListRef, mut map: M) -> NvResult<M> {
+    for pair in nv {
+        let name = pair
+            .name()
+            .to_str()
+            .map_err(|e| NvError::Io(io::Error::new(io::ErrorKind::InvalidData, e)))?;
+        map.extend(Some((name.to_owned(), V::decode(pair)?)));
+    }
+    Ok(map)
+}
+
+/// Decode each pair's value as a `V`, such as a [`NvValue`] to accept any type, or `u64` for
+/// lists like the output of `get_holds()`
+impl<V: NvDecode> TryFrom<&NvListRef> for BTreeMap<String, V> {
+    type Error = NvError;
+
+    fn try_from(nv: &NvListRef) -> NvResult<Self> {
+        decode_map(nv, BTreeMap::new())
+    }
+}
+
+/// Decode each pair's value as a `V`, as for `BTreeMap`
+impl<V: NvDecode> TryFrom<&NvListRef> for HashMap<String, V> {
+    type Error = NvError;
+
+    fn try_from(nv: &NvListRef) -> NvResult<Self> {
+        decode_map(nv, HashMap::new())
+    }
+}
+
+impl NvEncode for NvValue {
+    fn insert_into<S: CStrArgument>(&self, name: S, nv: &mut NvListRef) -> NvResult<()> {
+        match self {
+            NvValue::Bool => nv.insert(name, &()),
+            NvValue::BoolV(v) => nv.insert(name, v),
+            NvValue::Byte(v) => {
+                let name = crate::error::cstr(name)?;
+                let v =
+                    unsafe { sys::nvlist_add_byte(nv.as_mut_ptr(), name.as_ref().as_ptr(), *v) };
+                if v != 0 {
+                    Err(NvError::from_errno(v))
+                } else {
+                    Ok(())
+                }
+            }
+            NvValue::Int8(v) => nv.insert(name, v),
+            NvValue::Uint8(v) => nv.insert(name, v),
+            NvValue::Int16(v) => nv.insert(name, v),
+            NvValue::Uint16(v) => nv.insert(name, v),
+            NvValue::Int32(v) => nv.insert(name, v),
+            NvValue::Uint32(v) => nv.insert(name, v),
+            NvValue::Int64(v) => nv.insert(name, v),
+            NvValue::Uint64(v) => nv.insert(name, v),
+            NvValue::Str(v) => nv.insert(name, v.as_c_str()),
+            NvValue::NvList(pairs) => nv.insert(name, &*list_from_pairs(pairs)?),
+            NvValue::ByteArray(v) => {
+                let name = crate::error::cstr(name)?;
+                let v = unsafe {
+                    sys::nvlist_add_byte_array(
+                        nv.as_mut_ptr(),
+                        name.as_ref().as_ptr(),
+                        v.as_ptr() as *mut u8,
+                        v.len() as u32,
+                    )
+                };
+                if v != 0 {
+                    Err(NvError::from_errno(v))
+                } else {
+                    Ok(())
+                }
+            }
+            NvValue::Int8Array(v) => nv.insert(name, &v[..]),
+            NvValue::Uint8Array(v) => nv.insert(name, &v[..]),
+            NvValue::Int16Array(v) => nv.insert(name, &v[..]),
+            NvValue::Uint16Array(v) => nv.insert(name, &v[..]),
+            NvValue::Int32Array(v) => nv.insert(name, &v[..]),
+            NvValue::Uint32Array(v) => nv.insert(name, &v[..]),
+            NvValue::Int64Array(v) => nv.insert(name, &v[..]),
+            NvValue::Uint64Array(v) => nv.insert(name, &v[..]),
+            NvValue::NvListArray(lists) => {
+                let lists = lists
+                    .iter()
+                    .map(|pairs| list_from_pairs(pairs))
+                    .collect::<NvResult<Vec<_>>>()?;
+                nv.insert(name, &lists[..])
+            }
+        }
+    }
+}
+
+impl NvDecode for NvValue {
+    fn decode(pair: &NvPair) -> NvResult<Self> {
+        pair.data().to_value()
+    }
+}
@@ -98,3 +98,671 @@ fn insert_cstr() {
     a.insert("hello", CStr::from_bytes_with_nul(b"bye\0").unwrap())
         .unwrap();
 }
+
+#[test]
+fn to_value_round_trip() {
+    use std::convert::TryFrom;
+
+    let mut inner = nvpair::NvList::new_unique_names();
+    inner.insert("path", "/dev/sda").unwrap();
+    inner.insert("guid", &5u64).unwrap();
+
+    let mut a = nvpair::NvList::new_unique_names();
+    a.insert("vdev_tree", inner.as_ref()).unwrap();
+    a.insert("txgs", &[1u64, 2, 3][..]).unwrap();
+    a.insert("active", &true).unwrap();
+
+    let v = a.to_value().unwrap();
+    let tree = v.get("vdev_tree").unwrap();
+    assert_eq!(tree.get("guid"), Some(&nvpair::NvValue::Uint64(5)));
+    assert_eq!(
+        v.get("txgs"),
+        Some(&nvpair::NvValue::Uint64Array(vec![1, 2, 3]))
+    );
+
+    let b = nvpair::NvList::try_from(&v).unwrap();
+    assert_eq!(b.to_value().unwrap(), v);
+
+    // values are independent of the list they were read from
+    drop(a);
+    drop(b);
+    let v2 = v.clone();
+    assert_eq!(
+        std::thread::spawn(move || v2).join().unwrap().get("active"),
+        Some(&nvpair::NvValue::BoolV(true))
+    );
+}
+
+#[test]
+fn value_not_a_list() {
+    use std::convert::TryFrom;
+
+    let e = nvpair::NvList::try_from(&nvpair::NvValue::Uint64(1)).unwrap_err();
+    assert_eq!(
+        std::io::Error::from(e).kind(),
+        std::io::ErrorKind::InvalidInput
+    );
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn json_round_trip() {
+    let mut child = nvpair::NvList::new_unique_names();
+    child.insert("path", "/dev/sda").unwrap();
+
+    let mut a = nvpair::NvList::new_unique_names();
+    a.insert("u8", &7u8).unwrap();
+    a.insert("i64", &-7i64).unwrap();
+    a.insert("u64", &u64::MAX).unwrap();
+    a.insert("present", &()).unwrap();
+    a.insert("bytes", &[0xde_u8, 0xad][..]).unwrap();
+    a.insert("vdev", child.as_ref()).unwrap();
+
+    let j = a.to_json().unwrap();
+    assert_eq!(j["u8"], serde_json::json!({ "uint8": 7 }));
+    assert_eq!(j["u64"], serde_json::json!({ "uint64": u64::MAX }));
+    assert_eq!(j["present"], serde_json::json!({ "boolean": null }));
+    assert_eq!(
+        j["vdev"],
+        serde_json::json!({ "nvlist": { "path": { "string": "/dev/sda" } } })
+    );
+
+    let b = nvpair::NvList::from_json(&j).unwrap();
+    assert_eq!(b.to_json().unwrap(), j);
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn json_rejects_untagged() {
+    let e = nvpair::NvList::from_json(&serde_json::json!({ "a": 1 })).unwrap_err();
+    assert!(
+        matches!(e, nvpair::NvError::Io(ref e) if e.kind() == std::io::ErrorKind::InvalidInput)
+    );
+
+    let e =
+        nvpair::NvList::from_json(&serde_json::json!({ "a": { "string": "nul\0" } })).unwrap_err();
+    assert!(matches!(e, nvpair::NvError::InteriorNul));
+}
+
+#[test]
+fn pretty() {
+    let mut disk = nvpair::NvList::new_unique_names();
+    disk.insert("type", "disk").unwrap();
+    disk.insert("guid", &42u64).unwrap();
+
+    let mut tree = nvpair::NvList::new_unique_names();
+    tree.insert("type", "root").unwrap();
+    let children = match disk.to_value().unwrap() {
+        nvpair::NvValue::NvList(pairs) => nvpair::NvValue::NvListArray(vec![pairs]),
+        _ => unreachable!(),
+    };
+    tree.insert("children", &children).unwrap();
+
+    let mut a = nvpair::NvList::new_unique_names();
+    a.insert("name", "tank").unwrap();
+    a.insert("bytes", &[1u8, 0xff][..]).unwrap();
+    a.insert("vdev_tree", tree.as_ref()).unwrap();
+
+    assert_eq!(
+        a.to_string(),
+        "name: 'tank'\n\
+         bytes: [1 255]\n\
+         vdev_tree:\n    \
+             type: 'root'\n    \
+             children[0]:\n        \
+                 type: 'disk'\n        \
+                 guid: 42\n"
+    );
+
+    assert_eq!(
+        a.pretty().max_depth(0).hex_bytes(true).to_string(),
+        "name: 'tank'\n\
+         bytes: 0x01ff\n\
+         vdev_tree: <nvlist, 2 pairs>\n"
+    );
+}
+
+fn pool_config() -> nvpair::NvList {
+    nvpair::nvlist! {
+        "vdev_tree" => nvpair::nvlist! {
+            "children" => [nvpair::nvlist! { "path" => "/dev/sda1" }],
+            "guids" => [10u64, 20],
+        },
+    }
+}
+
+#[test]
+fn lookup_path() {
+    let config = pool_config();
+
+    match config.lookup_path("vdev_tree/children/0/path").unwrap() {
+        nvpair::NvData::Str(s) => assert_eq!(s.to_str().unwrap(), "/dev/sda1"),
+        d => panic!("unexpected data: {:?}", d),
+    }
+
+    match config.lookup_path("vdev_tree/guids/1").unwrap() {
+        nvpair::NvData::Uint64(v) => assert_eq!(v, 20),
+        d => panic!("unexpected data: {:?}", d),
+    }
+
+    match config.lookup_path("vdev_tree/children/1/path").unwrap_err() {
+        nvpair::NvError::NotFound { name } => assert_eq!(name.to_str().unwrap(), "1"),
+        e => panic!("unexpected error: {}", e),
+    }
+    match config.lookup_path("vdev_tree/children/x").unwrap_err() {
+        nvpair::NvError::NotFound { name } => assert_eq!(name.to_str().unwrap(), "x"),
+        e => panic!("unexpected error: {}", e),
+    }
+    match config.lookup_path("vdev_tree/missing").unwrap_err() {
+        nvpair::NvError::NotFound { name } => assert_eq!(name.to_str().unwrap(), "missing"),
+        e => panic!("unexpected error: {}", e),
+    }
+    match config.lookup_path("vdev_tree/guids/0/x").unwrap_err() {
+        nvpair::NvError::TypeMismatch { name, found, .. } => {
+            assert_eq!(name.to_str().unwrap(), "0");
+            assert_eq!(found, "uint64");
+        }
+        e => panic!("unexpected error: {}", e),
+    }
+}
+
+#[test]
+fn insert_path() {
+    let mut config = pool_config();
+
+    config
+        .insert_path("vdev_tree/children/0/path", "/dev/sdb1")
+        .unwrap();
+    config
+        .insert_path("vdev_tree/children/0/whole_disk", &1u64)
+        .unwrap();
+
+    let disk = config.lookup_path_mut("vdev_tree/children/0").unwrap();
+    assert_eq!(disk.lookup_uint64("whole_disk").unwrap(), 1);
+    assert_eq!(
+        disk.lookup_string("path").unwrap().to_str().unwrap(),
+        "/dev/sdb1"
+    );
+}
+
+#[test]
+fn lookup_nvlist_borrows() {
+    let mut config = pool_config();
+
+    {
+        let tree = config.lookup_nvlist("vdev_tree").unwrap();
+        let children = tree.lookup_nvlist_array("children").unwrap();
+        assert_eq!(children.len(), 1);
+        assert_eq!(
+            children[0].lookup_string("path").unwrap().to_str().unwrap(),
+            "/dev/sda1"
+        );
+    }
+
+    // dropping the borrowed lists above must not have freed anything owned by `config`
+    let copy = config
+        .lookup_nvlist("vdev_tree")
+        .unwrap()
+        .try_to_owned()
+        .unwrap();
+    config
+        .lookup_nvlist_mut("vdev_tree")
+        .unwrap()
+        .insert("ashift", &12u64)
+        .unwrap();
+
+    assert!(!copy.exists("ashift"));
+    match config.lookup_path("vdev_tree/ashift").unwrap() {
+        nvpair::NvData::Uint64(v) => assert_eq!(v, 12),
+        d => panic!("unexpected data: {:?}", d),
+    }
+    assert_eq!(copy.lookup_nvlist_array("children").unwrap().len(), 1);
+}
+
+#[test]
+fn remove_retain_merge() {
+    let mut a = nvpair::NvList::new_unique_names();
+    a.insert("compression", "lz4").unwrap();
+    a.insert("recordsize", &131072u64).unwrap();
+    a.insert("atime", "off").unwrap();
+
+    a.remove("atime").unwrap();
+    assert!(!a.exists("atime"));
+    assert!(matches!(
+        a.remove("atime"),
+        Err(nvpair::NvError::NotFound { .. })
+    ));
+
+    let mut b = nvpair::NvList::new_unique_names();
+    b.insert("compression", "zstd").unwrap();
+    b.insert("quota", &(1u64 << 30)).unwrap();
+    a.merge(&b).unwrap();
+    assert_eq!(
+        a.lookup_string("compression").unwrap().to_str().unwrap(),
+        "zstd"
+    );
+    assert_eq!(a.lookup_uint64("quota").unwrap(), 1 << 30);
+
+    a.retain(|p| p.name().to_bytes() != b"quota").unwrap();
+    let names: Vec<_> = a.iter().map(|p| p.name().to_owned()).collect();
+    assert_eq!(names.len(), 2);
+    assert!(!a.exists("quota"));
+}
+
+#[test]
+fn remove_non_unique() {
+    let mut a = nvpair::NvList::new();
+    a.insert("dup", &1u64).unwrap();
+    a.insert("dup", &2u64).unwrap();
+    a.insert("other", &3u64).unwrap();
+
+    a.remove("dup").unwrap();
+    let left: Vec<_> = a
+        .iter()
+        .map(|p| (p.name().to_str().unwrap().to_owned(), p.as_u64().unwrap()))
+        .collect();
+    assert_eq!(left, vec![("dup".to_owned(), 2), ("other".to_owned(), 3)]);
+
+    a.remove("dup").unwrap();
+    assert!(matches!(
+        a.remove("dup"),
+        Err(nvpair::NvError::NotFound { .. })
+    ));
+    assert_eq!(a.iter().count(), 1);
+}
+
+#[test]
+fn insert_or_replace() {
+    let mut a = nvpair::NvList::new();
+    a.insert("dup", &1u64).unwrap();
+    a.insert("dup", &2u64).unwrap();
+    assert_eq!(a.iter().count(), 2);
+
+    a.insert_or_replace("dup", "three").unwrap();
+    assert_eq!(a.iter().count(), 1);
+    // `lookup_*()` needs NV_UNIQUE_NAME, so find the pair by walking the list
+    let dup = a.iter().find(|p| p.name().to_bytes() == b"dup").unwrap();
+    assert_eq!(dup.as_str().unwrap().to_str().unwrap(), "three");
+
+    a.insert_or_replace("new", &4u64).unwrap();
+    a.remove_all("dup").unwrap();
+    assert_eq!(a.iter().count(), 1);
+    assert!(matches!(
+        a.remove_all("dup"),
+        Err(nvpair::NvError::NotFound { .. })
+    ));
+}
+
+#[test]
+fn nvlist_arrays() {
+    let a = nvpair::nvlist! { "path" => "/dev/sda1" };
+    let b = nvpair::nvlist! { "path" => "/dev/sdb1", "whole_disk" => 1u64 };
+
+    let mut nv = nvpair::NvList::new_unique_names();
+    nv.insert("owned", &[a.clone(), b.clone()][..]).unwrap();
+    nv.insert("borrowed", &[&*a, &*b][..]).unwrap();
+    nv.insert("empty", &nvpair::nvlist! {}).unwrap();
+
+    for name in &["owned", "borrowed"] {
+        let children = nv.lookup_nvlist_array(*name).unwrap();
+        assert_eq!(children.len(), 2);
+        assert_eq!(
+            children[1].lookup_string("path").unwrap().to_str().unwrap(),
+            "/dev/sdb1"
+        );
+        assert_eq!(children[1].lookup_uint64("whole_disk").unwrap(), 1);
+    }
+    assert!(nv.lookup_nvlist("empty").unwrap().is_empty());
+}
+
+#[test]
+fn builder_unique_name_type() {
+    let mut nv = nvpair::NvListBuilder::new()
+        .unique_name_type(true)
+        .build()
+        .unwrap();
+    nv.insert("a", &1u64).unwrap();
+    nv.insert("a", &2u64).unwrap();
+    nv.insert("a", "two").unwrap();
+
+    assert_eq!(nv.iter().count(), 2);
+    assert_eq!(nv.lookup_uint64("a").unwrap(), 2);
+}
+
+#[test]
+fn arena_allocator() {
+    let mut arena = nvpair::alloc::NvAllocator::arena();
+
+    for _ in 0..2 {
+        let mut lists = Vec::new();
+        for i in 0..1000u64 {
+            let mut nv = nvpair::NvListBuilder::new()
+                .unique_names(true)
+                .build_in(&arena)
+                .unwrap();
+            nv.insert("snapshot", "pool/fs@snap").unwrap();
+            nv.insert("n", &i).unwrap();
+            nv.insert("nested", &nvpair::nvlist! { "a" => [1u64, 2, 3] })
+                .unwrap();
+            lists.push(nv);
+        }
+
+        let copy = lists[999].try_to_owned().unwrap();
+        drop(lists);
+        assert_eq!(copy.lookup_uint64("n").unwrap(), 999);
+        arena.reset();
+    }
+}
+
+#[test]
+fn pack_into_read_from() {
+    let config = nvpair::nvlist! {
+        "name" => "tank",
+        "vdev_tree" => nvpair::nvlist! {
+            "type" => "root",
+            "children" => [
+                nvpair::nvlist! { "path" => "/dev/sda1", "guid" => 1u64 },
+                nvpair::nvlist! {
+                    "path" => "/dev/sdb1",
+                    "stats" => nvpair::nvlist! { "errors" => 0u64 },
+                },
+            ],
+        },
+        "features" => nvpair::nvlist! {},
+        "txg" => 4u64,
+    };
+
+    for &code in &[nvpair::NvEncoding::Native, nvpair::NvEncoding::Xdr] {
+        let mut stream = Vec::new();
+        let n = config.pack_into(&mut stream, code).unwrap();
+        assert_eq!(n, config.pack(code).unwrap().len());
+        config.pack_into(&mut stream, code).unwrap();
+        stream.extend_from_slice(b"trailer");
+
+        let mut r = &stream[..];
+        for _ in 0..2 {
+            let nv = nvpair::NvList::read_from(&mut r).unwrap();
+            assert_eq!(nv.to_value().unwrap(), config.to_value().unwrap());
+        }
+        assert_eq!(r, b"trailer");
+
+        match nvpair::NvList::read_from(&mut &stream[..n - 1]).unwrap_err() {
+            nvpair::NvError::Io(e) => assert_eq!(e.kind(), std::io::ErrorKind::UnexpectedEof),
+            e => panic!("unexpected error: {}", e),
+        }
+    }
+}
+
+#[test]
+fn unpack_limited() {
+    use nvpair::{UnpackError, UnpackLimits};
+
+    let nv = nvpair::nvlist! {
+        "toname" => "pool/fs@snap",
+        "object" => 5u64,
+        "payload" => [0xdeu8, 0xad, 0xbe],
+        "props" => nvpair::nvlist! {
+            "inner" => nvpair::nvlist! { "a" => [1u32, 2] },
+        },
+    };
+    let limits = UnpackLimits::new();
+
+    for &code in &[nvpair::NvEncoding::Native, nvpair::NvEncoding::Xdr] {
+        let buf = nv.pack(code).unwrap();
+        let nv2 = nvpair::NvList::try_unpack_limited(&buf, &limits).unwrap();
+        assert_eq!(nv2.to_value().unwrap(), nv.to_value().unwrap());
+
+        match nvpair::NvList::try_unpack_limited(&buf[..buf.len() - 1], &limits) {
+            Err(UnpackError::Truncated { offset }) => assert_eq!(offset, buf.len() - 1),
+            r => panic!("unexpected result: {:?}", r),
+        }
+
+        let mut bad = buf.clone();
+        bad[0] = 7;
+        match nvpair::NvList::try_unpack_limited(&bad, &limits) {
+            Err(UnpackError::Corrupt { offset: 0, .. }) => {}
+            r => panic!("unexpected result: {:?}", r),
+        }
+
+        let mut long = buf.clone();
+        long.push(0);
+        let e = nvpair::NvList::try_unpack_limited(&long, &limits).unwrap_err();
+        assert_eq!(e.offset(), Some(buf.len()));
+
+        for limits in &[
+            UnpackLimits::new().max_depth(1),
+            UnpackLimits::new().max_pairs(4),
+            UnpackLimits::new().max_size(buf.len()),
+        ] {
+            match nvpair::NvList::try_unpack_limited(&buf, limits) {
+                Err(UnpackError::Limit { .. }) => {}
+                r => panic!("unexpected result for {:?}: {:?}", limits, r),
+            }
+        }
+        assert!(
+            nvpair::NvList::try_unpack_limited(&buf, &UnpackLimits::new().max_pairs(6)).is_ok()
+        );
+    }
+}
+
+#[test]
+fn unpack_limited_xdr_arrays() {
+    use nvpair::{NvValue, UnpackLimits};
+
+    let mut nv = nvpair::NvList::new_unique_names();
+    let values = vec![
+        ("bytes", NvValue::ByteArray(vec![1, 2, 3, 4, 5])),
+        ("i8", NvValue::Int8Array(vec![-1, 2, -3])),
+        (
+            "u8",
+            NvValue::Uint8Array(vec![0xde, 0xad, 0xbe, 0xef, 0x01]),
+        ),
+        ("i16", NvValue::Int16Array(vec![-1, 2, -3])),
+        ("u16", NvValue::Uint16Array(vec![0xffff, 2])),
+        ("i32", NvValue::Int32Array(vec![-1, 2, -3])),
+        ("u32", NvValue::Uint32Array(vec![1, 2])),
+        ("i64", NvValue::Int64Array(vec![-1, 2, -3])),
+        ("u64", NvValue::Uint64Array(vec![1, 2])),
+        ("empty-u8", NvValue::Uint8Array(vec![])),
+        ("empty-u64", NvValue::Uint64Array(vec![])),
+        ("empty-bytes", NvValue::ByteArray(vec![])),
+        (
+            "lists",
+            NvValue::NvListArray(vec![
+                vec![(CString::new("a").unwrap(), NvValue::Uint16Array(vec![1]))],
+                vec![],
+            ]),
+        ),
+    ];
+    for (name, v) in &values {
+        nv.insert(*name, v).unwrap();
+    }
+
+    let strs = [CString::new("one").unwrap(), CString::new("three").unwrap()];
+    let mut ptrs: Vec<_> = strs.iter().map(|s| s.as_ptr() as *mut _).collect();
+    let mut bools = vec![
+        nvpair_sys::boolean_t::B_TRUE,
+        nvpair_sys::boolean_t::B_FALSE,
+        nvpair_sys::boolean_t::B_TRUE,
+    ];
+    unsafe {
+        let name = CString::new("strings").unwrap();
+        let r = nvpair_sys::nvlist_add_string_array(
+            nv.as_mut_ptr(),
+            name.as_ptr(),
+            ptrs.as_mut_ptr(),
+            ptrs.len() as _,
+        );
+        assert_eq!(r, 0);
+        let name = CString::new("bools").unwrap();
+        let r = nvpair_sys::nvlist_add_boolean_array(
+            nv.as_mut_ptr(),
+            name.as_ptr(),
+            bools.as_mut_ptr(),
+            bools.len() as _,
+        );
+        assert_eq!(r, 0);
+        let name = CString::new("empty-bools").unwrap();
+        let r = nvpair_sys::nvlist_add_boolean_array(
+            nv.as_mut_ptr(),
+            name.as_ptr(),
+            bools.as_mut_ptr(),
+            0,
+        );
+        assert_eq!(r, 0);
+    }
+
+    let buf = nv.pack(nvpair::NvEncoding::Xdr).unwrap();
+    let nv2 = nvpair::NvList::try_unpack_limited(&buf, &UnpackLimits::new())
+        .unwrap_or_else(|e| panic!("{:?} unpacking {:?}", e, buf));
+    assert_eq!(nv2, nv);
+    for (name, v) in &values {
+        assert_eq!(&nv2.lookup(*name).unwrap().data().to_value().unwrap(), v);
+    }
+}
+
+#[test]
+fn typed_errors() {
+    let mut nv = nvpair::NvList::new_unique_names();
+    nv.insert("name", "tank").unwrap();
+
+    match nv.lookup_uint64("name").unwrap_err() {
+        nvpair::NvError::TypeMismatch {
+            name,
+            expected,
+            found,
+        } => {
+            assert_eq!(name.to_str().unwrap(), "name");
+            assert_eq!(expected, "uint64");
+            assert_eq!(found, "string");
+        }
+        e => panic!("unexpected error: {}", e),
+    }
+    assert!(matches!(
+        nv.lookup_string("guid"),
+        Err(nvpair::NvError::NotFound { .. })
+    ));
+    assert!(matches!(
+        nv.insert("bad", "nul\0byte"),
+        Err(nvpair::NvError::InteriorNul)
+    ));
+    assert!(matches!(
+        nv.insert("bad\0name", &1u64),
+        Err(nvpair::NvError::InteriorNul)
+    ));
+    assert!(matches!(
+        nvpair::NvList::try_unpack(b"not an nvlist"),
+        Err(nvpair::NvError::Corrupt { .. })
+    ));
+
+    let e: std::io::Error = nv.lookup("guid").unwrap_err().into();
+    assert_eq!(e.kind(), std::io::ErrorKind::NotFound);
+}
+
+#[test]
+fn eq_and_diff() {
+    let a = pool_config();
+    assert_eq!(a, a.try_clone().unwrap());
+
+    // unique name lists compare regardless of order
+    let x = nvpair::nvlist! { "a" => 1u64, "b" => "two" };
+    let y = nvpair::nvlist! { "b" => "two", "a" => 1u64 };
+    assert_eq!(x, y);
+    assert!(nvpair::diff(&x, &y).is_empty());
+
+    // names are matched exactly, not parsed as `name[index]`
+    let x = nvpair::nvlist! { "a[0]" => 1u64, "b" => 2u64 };
+    let y = nvpair::nvlist! { "b" => 2u64, "a[0]" => 1u64 };
+    assert_eq!(x, y);
+
+    // ...but other lists don't
+    let mut x = nvpair::NvList::new();
+    x.insert("a", &1u64).unwrap();
+    x.insert("b", &2u64).unwrap();
+    let mut y = nvpair::NvList::new();
+    y.insert("b", &2u64).unwrap();
+    y.insert("a", &1u64).unwrap();
+    assert_ne!(x, y);
+
+    let mut b = a.try_clone().unwrap();
+    b.insert_path("vdev_tree/children/0/path", "/dev/sdb1")
+        .unwrap();
+    b.insert_path("vdev_tree/guids", &[10u64, 20, 30][..])
+        .unwrap();
+    b.insert_path("vdev_tree/ashift", &12u64).unwrap();
+    b.insert("version", &5000u64).unwrap();
+    assert_ne!(a, b);
+
+    let d = nvpair::diff(&a, &b);
+    assert_eq!(d.added, ["vdev_tree/ashift", "version"]);
+    assert!(d.removed.is_empty());
+    assert_eq!(d.changed, ["vdev_tree/children/0/path", "vdev_tree/guids"]);
+
+    let d = nvpair::diff(&b, &a);
+    assert_eq!(d.removed, ["vdev_tree/ashift", "version"]);
+}
+
+#[test]
+fn typed_getters() {
+    let nv = nvpair::nvlist! {
+        "guid" => 12u64,
+        "name" => "tank",
+        "children" => [nvpair::nvlist! { "path" => "/dev/sda1" }],
+        "guids" => [10u64, 20],
+    };
+
+    let guid = nv.lookup("guid").unwrap();
+    assert_eq!(guid.data_type(), nvpair::NvType::Uint64);
+    assert_eq!(guid.as_u64(), Some(12));
+    assert_eq!(guid.as_u32(), None);
+    assert_eq!(guid.as_str(), None);
+
+    let name = nv.lookup("name").unwrap();
+    assert_eq!(name.data_type().name(), "string");
+    assert_eq!(name.as_str().unwrap().to_str().unwrap(), "tank");
+
+    let children = nv.lookup("children").unwrap();
+    assert_eq!(children.data_type(), nvpair::NvType::NvListArray);
+    let children = children.as_list_array().unwrap();
+    assert_eq!(children.len(), 1);
+    assert_eq!(
+        children[0].lookup_string("path").unwrap().to_str().unwrap(),
+        "/dev/sda1"
+    );
+
+    let guids = nv.lookup("guids").unwrap();
+    assert_eq!(guids.as_u64_array(), Some(&[10u64, 20][..]));
+    assert_eq!(guids.as_list(), None);
+}
+
+#[test]
+fn collections() {
+    use std::collections::{BTreeMap, HashMap};
+    use std::convert::TryFrom;
+
+    let mut nv: nvpair::NvList = vec![("compression", "lz4"), ("atime", "off")]
+        .into_iter()
+        .collect();
+    nv.extend(vec![("checksum".to_owned(), "sha256")]);
+    assert_eq!(nv.lookup_string("atime").unwrap().to_str().unwrap(), "off");
+
+    let map = BTreeMap::<String, nvpair::NvValue>::try_from(&*nv).unwrap();
+    assert_eq!(
+        map.keys().map(|k| &k[..]).collect::<Vec<_>>(),
+        ["atime", "checksum", "compression"]
+    );
+    assert_eq!(
+        map["compression"],
+        nvpair::NvValue::Str(std::ffi::CString::new("lz4").unwrap())
+    );
+
+    let holds: nvpair::NvList = (0..3u64).map(|i| (format!("hold{}", i), i)).collect();
+    let holds = HashMap::<String, u64>::try_from(&*holds).unwrap();
+    assert_eq!(holds.len(), 3);
+    assert_eq!(holds["hold2"], 2);
+
+    match HashMap::<String, u64>::try_from(&*nv).unwrap_err() {
+        nvpair::NvError::TypeMismatch { expected, .. } => assert_eq!(expected, "uint64"),
+        e => panic!("unexpected error: {}", e),
+    }
+}
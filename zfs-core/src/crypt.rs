@@ -0,0 +1,610 @@
+//! Native encryption: key formats, wrapping keys and creating encrypted datasets
+//!
+//! libzfs_core never reads keys itself. The caller supplies the key material, and we turn it
+//! into the 32 byte wrapping key that the kernel expects, the same way `zfs(8)` does:
+//!
+//!  - `raw` keys are used as is
+//!  - `hex` keys are decoded from 64 hex digits
+//!  - `passphrase` keys are run through PBKDF2-HMAC-SHA1, using the dataset's `pbkdf2salt` and
+//!    `pbkdf2iters` properties
+use crate::Zfs;
+use cstr_argument::CStrArgument;
+use nvpair::{NvData, NvList, NvListRef};
+use std::convert::TryInto;
+use std::{fmt, io, ops};
+use zeroize::Zeroize;
+use zfs_core_sys as sys;
+
+/// Length of the key used to wrap a dataset's master keys (`WRAPPING_KEY_LEN`)
+pub const WRAPPING_KEY_LEN: usize = 32;
+
+const MIN_PASSPHRASE_LEN: usize = 8;
+const MAX_PASSPHRASE_LEN: usize = 512;
+
+fn invalid_input<E: Into<Box<dyn std::error::Error + Send + Sync>>>(e: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, e)
+}
+
+/// How the key material of a dataset is encoded (the `keyformat` property)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyFormat {
+    /// 32 raw bytes
+    Raw,
+    /// 64 hex digits
+    Hex,
+    /// A passphrase of 8 to 512 bytes
+    Passphrase,
+}
+
+impl KeyFormat {
+    pub fn as_raw(&self) -> sys::zfs_keyformat_t {
+        match self {
+            KeyFormat::Raw => sys::zfs_keyformat::ZFS_KEYFORMAT_RAW,
+            KeyFormat::Hex => sys::zfs_keyformat::ZFS_KEYFORMAT_HEX,
+            KeyFormat::Passphrase => sys::zfs_keyformat::ZFS_KEYFORMAT_PASSPHRASE,
+        }
+    }
+
+    /// Returns `None` for `ZFS_KEYFORMAT_NONE` (an unencrypted dataset) and unknown values
+    pub fn from_raw(v: sys::zfs_keyformat_t) -> Option<Self> {
+        match v {
+            sys::zfs_keyformat::ZFS_KEYFORMAT_RAW => Some(KeyFormat::Raw),
+            sys::zfs_keyformat::ZFS_KEYFORMAT_HEX => Some(KeyFormat::Hex),
+            sys::zfs_keyformat::ZFS_KEYFORMAT_PASSPHRASE => Some(KeyFormat::Passphrase),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for KeyFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            KeyFormat::Raw => "raw",
+            KeyFormat::Hex => "hex",
+            KeyFormat::Passphrase => "passphrase",
+        })
+    }
+}
+
+/// Where `zfs load-key` finds the key material of a dataset (the `keylocation` property)
+///
+/// This is only recorded on the dataset: the key material is always passed to this library
+/// directly.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyLocation {
+    /// Ask the user (`prompt`)
+    Prompt,
+    /// Read from a URI, such as `file:///etc/zfs/keys/tank.key`
+    Uri(String),
+}
+
+impl KeyLocation {
+    pub fn as_raw(&self) -> sys::zfs_keylocation_t {
+        match self {
+            KeyLocation::Prompt => sys::zfs_key_location::ZFS_KEYLOCATION_PROMPT,
+            KeyLocation::Uri(_) => sys::zfs_key_location::ZFS_KEYLOCATION_URI,
+        }
+    }
+}
+
+impl fmt::Display for KeyLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyLocation::Prompt => f.write_str("prompt"),
+            KeyLocation::Uri(u) => f.write_str(u),
+        }
+    }
+}
+
+/// The cipher used for a dataset's data (the `encryption` property)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EncryptionAlgorithm {
+    Aes128Ccm,
+    Aes192Ccm,
+    Aes256Ccm,
+    Aes128Gcm,
+    Aes192Gcm,
+    Aes256Gcm,
+}
+
+impl EncryptionAlgorithm {
+    /// The value of this algorithm in `enum zio_encrypt`
+    pub fn as_raw(&self) -> u64 {
+        match self {
+            EncryptionAlgorithm::Aes128Ccm => 3,
+            EncryptionAlgorithm::Aes192Ccm => 4,
+            EncryptionAlgorithm::Aes256Ccm => 5,
+            EncryptionAlgorithm::Aes128Gcm => 6,
+            EncryptionAlgorithm::Aes192Gcm => 7,
+            EncryptionAlgorithm::Aes256Gcm => 8,
+        }
+    }
+}
+
+/// `aes-256-gcm`, which is what `encryption=on` selects
+impl Default for EncryptionAlgorithm {
+    fn default() -> Self {
+        EncryptionAlgorithm::Aes256Gcm
+    }
+}
+
+/// Key material (a raw key, hex digits or a passphrase), which is zeroed when dropped
+#[derive(Clone, Default)]
+pub struct KeyMaterial(Vec<u8>);
+
+impl KeyMaterial {
+    /// Take ownership of `v`, so that no other copy is left behind
+    pub fn new(v: Vec<u8>) -> Self {
+        KeyMaterial(v)
+    }
+}
+
+impl From<&[u8]> for KeyMaterial {
+    fn from(v: &[u8]) -> Self {
+        KeyMaterial(v.to_vec())
+    }
+}
+
+impl From<Vec<u8>> for KeyMaterial {
+    fn from(v: Vec<u8>) -> Self {
+        KeyMaterial(v)
+    }
+}
+
+impl From<&str> for KeyMaterial {
+    fn from(v: &str) -> Self {
+        KeyMaterial(v.as_bytes().to_vec())
+    }
+}
+
+impl From<String> for KeyMaterial {
+    fn from(v: String) -> Self {
+        KeyMaterial(v.into_bytes())
+    }
+}
+
+impl ops::Deref for KeyMaterial {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl Drop for KeyMaterial {
+    fn drop(&mut self) {
+        self.0.zeroize()
+    }
+}
+
+impl fmt::Debug for KeyMaterial {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("KeyMaterial(..)")
+    }
+}
+
+/// The key which wraps (encrypts) a dataset's master keys. Zeroed when dropped.
+pub struct WrappingKey([u8; WRAPPING_KEY_LEN]);
+
+impl WrappingKey {
+    /// Derive the wrapping key from key material in the given format
+    ///
+    /// `salt` and `iters` are the dataset's `pbkdf2salt` and `pbkdf2iters`, and are only used for
+    /// passphrases.
+    pub fn derive(format: KeyFormat, material: &[u8], salt: u64, iters: u64) -> io::Result<Self> {
+        let mut key = [0u8; WRAPPING_KEY_LEN];
+        match format {
+            KeyFormat::Raw => {
+                if material.len() != WRAPPING_KEY_LEN {
+                    return Err(invalid_input("raw keys must be 32 bytes"));
+                }
+                key.copy_from_slice(material);
+            }
+            KeyFormat::Hex => {
+                if material.len() != WRAPPING_KEY_LEN * 2 {
+                    return Err(invalid_input("hex keys must be 64 hex digits"));
+                }
+                for (k, digits) in key.iter_mut().zip(material.chunks(2)) {
+                    *k = (hex_digit(digits[0])? << 4) | hex_digit(digits[1])?;
+                }
+            }
+            KeyFormat::Passphrase => {
+                if material.len() < MIN_PASSPHRASE_LEN || material.len() > MAX_PASSPHRASE_LEN {
+                    return Err(invalid_input("passphrases must be 8 to 512 bytes"));
+                }
+                if iters < u64::from(sys::MIN_PBKDF2_ITERATIONS) {
+                    return Err(invalid_input("pbkdf2iters is below the minimum of 100000"));
+                }
+                let iters: u32 = iters
+                    .try_into()
+                    .map_err(|_| invalid_input("pbkdf2iters is too large"))?;
+                // zfs hashes the salt as a little endian u64
+                pbkdf2::pbkdf2::<hmac::Hmac<sha1::Sha1>>(
+                    material,
+                    &salt.to_le_bytes(),
+                    iters,
+                    &mut key,
+                );
+            }
+        }
+        Ok(WrappingKey(key))
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl Drop for WrappingKey {
+    fn drop(&mut self) {
+        self.0.zeroize()
+    }
+}
+
+impl fmt::Debug for WrappingKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("WrappingKey(..)")
+    }
+}
+
+fn hex_digit(c: u8) -> io::Result<u8> {
+    match c {
+        b'0'..=b'9' => Ok(c - b'0'),
+        b'a'..=b'f' => Ok(c - b'a' + 10),
+        b'A'..=b'F' => Ok(c - b'A' + 10),
+        _ => Err(invalid_input("hex keys may only contain hex digits")),
+    }
+}
+
+/// Everything needed to create an encrypted dataset, for
+/// [`Zfs::create_encrypted()`](crate::Zfs::create_encrypted)
+///
+/// ```no_run
+/// use zfs_core::{EncryptionParams, KeyFormat, KeyLocation};
+/// let params = EncryptionParams::new(KeyFormat::Passphrase, &b"correct horse battery staple"[..])
+///     .location(KeyLocation::Prompt);
+/// ```
+#[derive(Clone)]
+pub struct EncryptionParams {
+    format: KeyFormat,
+    material: KeyMaterial,
+    location: KeyLocation,
+    algorithm: EncryptionAlgorithm,
+    pbkdf2_iters: u64,
+    pbkdf2_salt: Option<u64>,
+}
+
+impl EncryptionParams {
+    /// Encrypt with `material` (in `format`) as the key, using `keylocation=prompt` and
+    /// `encryption=aes-256-gcm` unless changed
+    pub fn new<M: Into<KeyMaterial>>(format: KeyFormat, material: M) -> Self {
+        EncryptionParams {
+            format,
+            material: material.into(),
+            location: KeyLocation::Prompt,
+            algorithm: EncryptionAlgorithm::default(),
+            pbkdf2_iters: u64::from(sys::DEFAULT_PBKDF2_ITERATIONS),
+            pbkdf2_salt: None,
+        }
+    }
+
+    pub fn location(mut self, location: KeyLocation) -> Self {
+        self.location = location;
+        self
+    }
+
+    pub fn algorithm(mut self, algorithm: EncryptionAlgorithm) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+
+    /// PBKDF2 iterations for passphrases. Defaults to 350000, and may not be less than 100000.
+    pub fn pbkdf2_iters(mut self, iters: u64) -> Self {
+        self.pbkdf2_iters = iters;
+        self
+    }
+
+    /// PBKDF2 salt for passphrases. A random salt is used if not set.
+    pub fn pbkdf2_salt(mut self, salt: u64) -> Self {
+        self.pbkdf2_salt = Some(salt);
+        self
+    }
+
+    /// Add the encryption properties to `props`, and derive the wrapping key to pass with them
+    pub(crate) fn apply(&self, props: &mut NvListRef) -> io::Result<WrappingKey> {
+        props.insert("encryption", &self.algorithm.as_raw())?;
+        self.apply_key(props)
+    }
+
+    /// Like `apply()`, without the `encryption` property (which can't be changed)
+    fn apply_key(&self, props: &mut NvListRef) -> io::Result<WrappingKey> {
+        props.insert("keyformat", &u64::from(self.format.as_raw()))?;
+        props.insert("keylocation", &*self.location.to_string())?;
+
+        let salt = self.pbkdf2_salt.unwrap_or_else(rand::random);
+        if self.format == KeyFormat::Passphrase {
+            props.insert("pbkdf2iters", &self.pbkdf2_iters)?;
+            props.insert("pbkdf2salt", &salt)?;
+        }
+
+        WrappingKey::derive(self.format, &self.material, salt, self.pbkdf2_iters)
+    }
+}
+
+impl fmt::Debug for EncryptionParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EncryptionParams")
+            .field("format", &self.format)
+            .field("location", &self.location)
+            .field("algorithm", &self.algorithm)
+            .field("pbkdf2_iters", &self.pbkdf2_iters)
+            .field("pbkdf2_salt", &self.pbkdf2_salt)
+            .finish()
+    }
+}
+
+/// How [`KeyManager::change()`] changes a dataset's key (`dcp_cmd_t`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CryptCmd {
+    /// Wrap the dataset's master keys with a new key, making it an encryption root
+    /// (`DCP_CMD_NEW_KEY`)
+    NewKey,
+    /// Inherit the key of the parent's encryption root (`DCP_CMD_INHERIT`)
+    Inherit,
+    /// Like `NewKey`, but also for a dataset which isn't already an encryption root
+    /// (`DCP_CMD_FORCE_NEW_KEY`)
+    ForceNewKey,
+}
+
+impl CryptCmd {
+    pub fn as_raw(&self) -> u64 {
+        match self {
+            CryptCmd::NewKey => 2,
+            CryptCmd::Inherit => 3,
+            CryptCmd::ForceNewKey => 4,
+        }
+    }
+}
+
+/// Whether a dataset's key is loaded (the `keystatus` property)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyStatus {
+    /// The dataset isn't encrypted
+    None,
+    Unavailable,
+    Available,
+}
+
+impl KeyStatus {
+    pub fn from_raw(v: sys::zfs_keystatus_t) -> Option<Self> {
+        match v {
+            sys::zfs_keystatus::ZFS_KEYSTATUS_NONE => Some(KeyStatus::None),
+            sys::zfs_keystatus::ZFS_KEYSTATUS_UNAVAILABLE => Some(KeyStatus::Unavailable),
+            sys::zfs_keystatus::ZFS_KEYSTATUS_AVAILABLE => Some(KeyStatus::Available),
+            _ => None,
+        }
+    }
+
+    fn from_name(v: &str) -> Option<Self> {
+        match v {
+            "none" | "-" => Some(KeyStatus::None),
+            "unavailable" => Some(KeyStatus::Unavailable),
+            "available" => Some(KeyStatus::Available),
+            _ => None,
+        }
+    }
+}
+
+/// Reads the key related properties of `args.dataset`. Index properties may come back as either
+/// their name or their value.
+const KEY_PROPS_PROGRAM: &str = r#"
+args = ...
+local out = {}
+for _, p in ipairs({"keystatus", "keyformat", "pbkdf2salt", "pbkdf2iters"}) do
+    out[p] = zfs.get_prop(args["dataset"], p)
+end
+return out
+"#;
+
+// the same on Linux and FreeBSD
+const EACCES: i32 = 13;
+
+/// The encryption state of a dataset, as reported by [`KeyManager::key_props()`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyProps {
+    pub status: KeyStatus,
+    /// `None` if the dataset isn't encrypted
+    pub format: Option<KeyFormat>,
+    pub pbkdf2_salt: u64,
+    pub pbkdf2_iters: u64,
+}
+
+fn prop_u64(props: &NvListRef, name: &str) -> io::Result<u64> {
+    match props.lookup(name)?.data() {
+        NvData::Uint64(v) => Ok(v),
+        NvData::Int64(v) => Ok(v as u64),
+        d => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("unexpected value for {}: {:?}", name, d),
+        )),
+    }
+}
+
+fn prop_index<T, F, G>(props: &NvListRef, name: &str, from_raw: F, from_name: G) -> io::Result<T>
+where
+    F: Fn(u64) -> Option<T>,
+    G: Fn(&str) -> Option<T>,
+{
+    let pair = props.lookup(name)?;
+    let v = match pair.as_str() {
+        Some(s) => s.to_str().ok().and_then(from_name),
+        None => from_raw(prop_u64(props, name)?),
+    };
+    v.ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("unexpected value for {}: {:?}", name, pair.data()),
+        )
+    })
+}
+
+/// Loading, unloading, checking and changing the keys of encrypted datasets
+///
+/// Obtained from [`Zfs::key_manager()`]. Key material is derived into a wrapping key using the
+/// dataset's own `keyformat`, `pbkdf2salt` and `pbkdf2iters`, in the same way as
+/// `zfs load-key`. Both the key material and the derived key are zeroed once used.
+///
+/// Reading a dataset's key properties uses a channel program, which requires root privileges.
+///
+/// ```no_run
+/// # let zfs = zfs_core::Zfs::new().unwrap();
+/// let keys = zfs.key_manager();
+/// if keys.key_status("tank/secret")? == zfs_core::KeyStatus::Unavailable {
+///     keys.load("tank/secret", &b"correct horse battery staple"[..])?;
+/// }
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct KeyManager<'a> {
+    zfs: &'a Zfs,
+}
+
+impl<'a> KeyManager<'a> {
+    pub(crate) fn new(zfs: &'a Zfs) -> Self {
+        KeyManager { zfs }
+    }
+
+    /// Read the key related properties of `dataset`
+    pub fn key_props<S: CStrArgument>(&self, dataset: S) -> io::Result<KeyProps> {
+        let dataset = dataset.into_cstr();
+        let name = dataset
+            .as_ref()
+            .to_str()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+        let mut args = NvList::try_new()?;
+        args.insert("dataset", name)?;
+        let out = self.zfs.channel_program_nosync(
+            crate::pool_name(name),
+            KEY_PROPS_PROGRAM,
+            crate::ZCP_DEFAULT_INSTRLIMIT,
+            crate::ZCP_DEFAULT_MEMLIMIT,
+            &args,
+        )?;
+        let props = out.lookup_nvlist("return")?;
+
+        let format = prop_index(
+            props,
+            "keyformat",
+            |v| Some(v.try_into().ok().and_then(KeyFormat::from_raw)),
+            |s| match s {
+                "none" | "-" => Some(None),
+                "raw" => Some(Some(KeyFormat::Raw)),
+                "hex" => Some(Some(KeyFormat::Hex)),
+                "passphrase" => Some(Some(KeyFormat::Passphrase)),
+                _ => None,
+            },
+        )?;
+        Ok(KeyProps {
+            status: prop_index(
+                props,
+                "keystatus",
+                |v| v.try_into().ok().and_then(KeyStatus::from_raw),
+                KeyStatus::from_name,
+            )?,
+            format,
+            pbkdf2_salt: prop_u64(props, "pbkdf2salt")?,
+            pbkdf2_iters: prop_u64(props, "pbkdf2iters")?,
+        })
+    }
+
+    /// Whether the key of `dataset` is loaded
+    pub fn key_status<S: CStrArgument>(&self, dataset: S) -> io::Result<KeyStatus> {
+        Ok(self.key_props(dataset)?.status)
+    }
+
+    /// Derive the wrapping key for `dataset` from `material`, which is in the dataset's
+    /// `keyformat`
+    pub fn derive<S: CStrArgument, M: Into<KeyMaterial>>(
+        &self,
+        dataset: S,
+        material: M,
+    ) -> io::Result<WrappingKey> {
+        let material = material.into();
+        let props = self.key_props(dataset)?;
+        let format = props.format.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "dataset is not encrypted")
+        })?;
+        WrappingKey::derive(format, &material, props.pbkdf2_salt, props.pbkdf2_iters)
+    }
+
+    /// Load the key of the encryption root `dataset`
+    ///
+    /// Corresponds to `lzc_load_key()`
+    pub fn load<S: CStrArgument, M: Into<KeyMaterial>>(
+        &self,
+        dataset: S,
+        material: M,
+    ) -> io::Result<()> {
+        let dataset = dataset.into_cstr();
+        let key = self.derive(dataset.as_ref(), material)?;
+        self.zfs.load_key(dataset.as_ref(), false, key.as_bytes())
+    }
+
+    /// Check that `material` is the key of `dataset`, without loading it
+    ///
+    /// Returns `false` if the key is wrong (`EACCES`). If the key is already loaded, the kernel
+    /// refuses the check, and an `EEXIST` error (of kind `AlreadyExists`) is returned instead.
+    ///
+    /// Corresponds to `lzc_load_key()` with `noop` set
+    pub fn check<S: CStrArgument, M: Into<KeyMaterial>>(
+        &self,
+        dataset: S,
+        material: M,
+    ) -> io::Result<bool> {
+        let dataset = dataset.into_cstr();
+        let key = self.derive(dataset.as_ref(), material)?;
+        match self.zfs.load_key(dataset.as_ref(), true, key.as_bytes()) {
+            Ok(()) => Ok(true),
+            // not `PermissionDenied`, which also covers EPERM for unprivileged callers
+            Err(e) if e.raw_os_error() == Some(EACCES) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Unload the key of the encryption root `dataset`
+    ///
+    /// Corresponds to `lzc_unload_key()`
+    pub fn unload<S: CStrArgument>(&self, dataset: S) -> io::Result<()> {
+        self.zfs.unload_key(dataset)
+    }
+
+    /// Change the key of `dataset`, whose current key must be loaded
+    ///
+    /// `key` describes the new key for `CryptCmd::NewKey` and `CryptCmd::ForceNewKey`, and must be
+    /// `None` for `CryptCmd::Inherit`. Its algorithm is ignored, as that can't be changed.
+    ///
+    /// Corresponds to `lzc_change_key()`
+    pub fn change<S: CStrArgument>(
+        &self,
+        dataset: S,
+        cmd: CryptCmd,
+        key: Option<&EncryptionParams>,
+    ) -> io::Result<()> {
+        let mut props = NvList::try_new_unique_names()?;
+        let key = match (cmd, key) {
+            (CryptCmd::Inherit, None) => None,
+            (CryptCmd::Inherit, Some(_)) => {
+                return Err(invalid_input("a new key can't be given when inheriting"))
+            }
+            (_, Some(k)) => Some(k.apply_key(&mut props)?),
+            (_, None) => return Err(invalid_input("a new key is required")),
+        };
+        self.zfs.change_key(
+            dataset,
+            cmd.as_raw(),
+            &props,
+            key.as_ref().map(|k| k.as_bytes()),
+        )
+    }
+}
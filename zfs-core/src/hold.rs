@@ -0,0 +1,60 @@
+//! Holds which last as long as a guard value
+use crate::{Error, Zfs};
+use std::ffi::{CStr, CString};
+use std::fs::File;
+use std::os::unix::io::{AsRawFd, RawFd};
+
+/// The device which `lzc_hold()` cleanup fds must refer to
+pub(crate) const ZFS_DEV: &str = "/dev/zfs";
+
+/// User holds which are released when this guard is dropped
+///
+/// Returned by [`Zfs::hold_temporary()`]. The holds are tied to a cleanup fd (an open handle to
+/// `/dev/zfs`) owned by the guard. When that fd is closed, either by dropping the guard or by the
+/// process exiting for any reason, the kernel releases the holds.
+///
+/// The fd is opened with `O_CLOEXEC`, so child processes don't keep the holds alive.
+#[derive(Debug)]
+pub struct HoldGuard<'a> {
+    zfs: &'a Zfs,
+    cleanup: File,
+    holds: Vec<(CString, CString)>,
+}
+
+impl<'a> HoldGuard<'a> {
+    pub(crate) fn new(zfs: &'a Zfs, cleanup: File, holds: Vec<(CString, CString)>) -> Self {
+        HoldGuard {
+            zfs,
+            cleanup,
+            holds,
+        }
+    }
+
+    /// The `(snapshot, hold name)` pairs held by this guard
+    pub fn holds(&self) -> impl Iterator<Item = (&CStr, &CStr)> {
+        self.holds.iter().map(|(s, n)| (s.as_c_str(), n.as_c_str()))
+    }
+
+    /// The cleanup fd the holds are tied to
+    ///
+    /// Further holds may be tied to this fd by passing it to [`Zfs::hold()`], and will also be
+    /// released when the guard is dropped.
+    pub fn cleanup_fd(&self) -> RawFd {
+        self.cleanup.as_raw_fd()
+    }
+
+    /// Release the holds now, reporting any errors
+    ///
+    /// Dropping the guard also releases the holds, but can't report failures.
+    pub fn release(self) -> Result<(), Error> {
+        // a snapshot may have several of our holds, which must be released together
+        let mut holds: Vec<(&CStr, Vec<&CStr>)> = Vec::new();
+        for (snap, name) in &self.holds {
+            match holds.iter_mut().find(|(s, _)| *s == snap.as_c_str()) {
+                Some((_, names)) => names.push(name),
+                None => holds.push((snap, vec![name])),
+            }
+        }
+        self.zfs.release(&holds)
+    }
+}
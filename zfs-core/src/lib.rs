@@ -6,10 +6,41 @@ use nvpair::{NvList, NvListIter, NvListRef};
 use snafu::Snafu;
 use std::convert::TryInto;
 use std::marker::PhantomData;
-use std::os::unix::io::RawFd;
+use std::os::unix::io::{AsRawFd, RawFd};
 use std::{ffi, fmt, io, ptr};
 use zfs_core_sys as sys;
 
+mod crypt;
+mod hold;
+mod list;
+mod props;
+mod retention;
+mod snapshot;
+pub use crypt::{
+    CryptCmd, EncryptionAlgorithm, EncryptionParams, KeyFormat, KeyLocation, KeyManager,
+    KeyMaterial, KeyProps, KeyStatus, WrappingKey, WRAPPING_KEY_LEN,
+};
+pub use hold::HoldGuard;
+pub use list::{ListEntry, ListIter, ListOptions, ListType};
+pub use props::{
+    Cache, CanMount, Checksum, Compression, DatasetProp, DatasetProps, DnodeSize, LogBias, Prop,
+    PropEntry, PropSource, PropValue, RedundantMetadata, SnapDir, Sync, VolMode, Xattr,
+};
+pub use retention::{KeepReason, Kept, RetentionPlan, RetentionPolicy, SnapshotInfo};
+pub use snapshot::{SnapshotProps, TempSnapshot};
+
+/// Default limits for channel programs, as used by `zfs program`
+pub(crate) const ZCP_DEFAULT_INSTRLIMIT: u64 = 10 * 1000 * 1000;
+pub(crate) const ZCP_DEFAULT_MEMLIMIT: u64 = 10 * 1024 * 1024;
+
+/// The pool containing the dataset, snapshot or bookmark `name`
+pub(crate) fn pool_name(name: &str) -> &str {
+    match name.find(&['/', '@', '#'][..]) {
+        Some(i) => &name[..i],
+        None => name,
+    }
+}
+
 // TODO: consider splitting this into specific error kinds per operation
 #[derive(Debug, Snafu)]
 pub enum Error {
@@ -37,7 +68,7 @@ impl fmt::Debug for Zfs {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum DataSetType {
     Zfs,
     Zvol,
@@ -164,15 +195,65 @@ impl Zfs {
         name: S,
         dataset_type: DataSetType,
         props: &NvList,
+    ) -> io::Result<()> {
+        self.create_raw(name, dataset_type, props, None)
+    }
+
+    /// Create a new dataset with typed, checked properties
+    ///
+    /// The type of dataset created is the one `props` was built for.
+    ///
+    /// Corresponds to `lzc_create()`
+    #[doc(alias = "lzc_create")]
+    pub fn create_with_props<S: CStrArgument>(
+        &self,
+        name: S,
+        props: DatasetProps,
+    ) -> io::Result<()> {
+        let dataset_type = props.dataset_type();
+        self.create(name, dataset_type, &props.build()?)
+    }
+
+    /// Create a new dataset encrypted as described by `encryption`, with `props` set as
+    /// properties
+    ///
+    /// The `encryption`, `keyformat`, `keylocation` and (for passphrases) `pbkdf2iters` and
+    /// `pbkdf2salt` properties are set from `encryption`, and the wrapping key derived from its
+    /// key material is passed along. The key is loaded once this returns.
+    ///
+    /// Corresponds to `lzc_create()` with `wkeydata`
+    #[doc(alias = "lzc_create")]
+    pub fn create_encrypted<S: CStrArgument>(
+        &self,
+        name: S,
+        dataset_type: DataSetType,
+        props: &NvListRef,
+        encryption: &EncryptionParams,
+    ) -> io::Result<()> {
+        let mut props = props.try_to_owned()?;
+        let key = encryption.apply(&mut props)?;
+        self.create_raw(name, dataset_type, &props, Some(key.as_bytes()))
+    }
+
+    fn create_raw<S: CStrArgument>(
+        &self,
+        name: S,
+        dataset_type: DataSetType,
+        props: &NvListRef,
+        wkey: Option<&[u8]>,
     ) -> io::Result<()> {
         let name = name.into_cstr();
+        let (wkey, wkey_len) = match wkey {
+            Some(k) => (k.as_ptr() as *mut u8, k.len() as sys::uint_t),
+            None => (ptr::null_mut(), 0),
+        };
         let v = unsafe {
             sys::lzc_create(
                 name.as_ref().as_ptr(),
                 dataset_type.as_raw(),
                 props.as_ptr() as *mut _,
-                ptr::null_mut(),
-                0,
+                wkey,
+                wkey_len,
             )
         };
 
@@ -268,13 +349,28 @@ impl Zfs {
         &self,
         snaps: I,
     ) -> Result<(), Error> {
+        self.snapshot_with_props(snaps, SnapshotProps::new())
+    }
+
+    /// Create snapshot(s), setting user properties on each of them
+    ///
+    /// The same restrictions as [`snapshot()`](Zfs::snapshot) apply. The properties are set in
+    /// the same transaction that creates the snapshots.
+    ///
+    /// Corresponds to `lzc_snapshot()`.
+    #[doc(alias = "lzc_snapshot")]
+    pub fn snapshot_with_props<I: IntoIterator<Item = S>, S: CStrArgument>(
+        &self,
+        snaps: I,
+        props: SnapshotProps,
+    ) -> Result<(), Error> {
+        let props = props.build().map_err(|source| Error::Io { source })?;
         let mut arg = NvList::new();
 
         for i in snaps {
             arg.insert(i.into_cstr().as_ref(), &()).unwrap();
         }
 
-        let props = NvList::new();
         match self.snapshot_raw(&arg, &props) {
             Ok(()) => Ok(()),
             Err(Ok(v)) => Err(Error::Io { source: v }),
@@ -282,6 +378,109 @@ impl Zfs {
         }
     }
 
+    /// Create a uniquely named snapshot of `fs`, which is destroyed when the returned guard is
+    /// dropped (including while unwinding from a panic)
+    ///
+    /// ```no_run
+    /// # let zfs = zfs_core::Zfs::new().unwrap();
+    /// let snap = zfs.temp_snapshot("tank/data")?;
+    /// // ... send snap.name() ...
+    /// drop(snap);
+    /// # Ok::<(), zfs_core::Error>(())
+    /// ```
+    ///
+    /// Corresponds to `lzc_snapshot()`.
+    #[doc(alias = "lzc_snapshot")]
+    pub fn temp_snapshot<S: CStrArgument>(&self, fs: S) -> Result<TempSnapshot<'_>, Error> {
+        let fs = fs.into_cstr();
+        let fs = fs.as_ref().to_str().map_err(|e| Error::Io {
+            source: io::Error::new(io::ErrorKind::InvalidInput, e),
+        })?;
+        snapshot::temp_snapshot(self, fs)
+    }
+
+    /// Snapshot a dataset and all of its descendant filesystems and volumes
+    ///
+    /// `snapname` is the snapshot of the top dataset (`pool/fs@tag`), and every descendant gets a
+    /// snapshot with the same tag. `props` are set on each snapshot.
+    ///
+    /// Descendants are found with [`list()`](Zfs::list) and snapshotted with a single
+    /// `lzc_snapshot()` call, so the snapshots are created atomically, but a descendant created
+    /// after the listing gets no snapshot. When there are many descendants, a channel program which
+    /// finds and snapshots them in the same transaction is used instead.
+    ///
+    /// Either way, if any snapshot (or property) fails, no snapshots are left behind and the
+    /// failures are returned in [`Error::List`]. In the rare case that the channel program can't
+    /// destroy a snapshot it already created after a later one failed, that snapshot is also
+    /// listed there, and is left behind.
+    #[doc(alias = "lzc_snapshot")]
+    pub fn snapshot_recursive<S: CStrArgument>(
+        &self,
+        snapname: S,
+        props: SnapshotProps,
+    ) -> Result<(), Error> {
+        let props = props.build().map_err(|source| Error::Io { source })?;
+        let snapname = snapname.into_cstr();
+        let (root, tag) = snapname
+            .as_ref()
+            .to_str()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+            .and_then(snapshot::split_snapshot)
+            .map_err(|source| Error::Io { source })?;
+
+        let opts = ListOptions {
+            recursive: true,
+            ..Default::default()
+        };
+        let mut snaps = NvList::new();
+        let mut count = 0;
+        for e in self.list(root, opts) {
+            let e = e.map_err(|source| Error::Io { source })?;
+            count += 1;
+            if count > snapshot::MAX_LZC_SNAPSHOTS {
+                return self.snapshot_recursive_program(root, tag, &props);
+            }
+            snaps
+                .insert(format!("{}@{}", e.name, tag), &())
+                .map_err(|e| Error::Io { source: e.into() })?;
+        }
+
+        match self.snapshot_raw(&snaps, &props) {
+            Ok(()) => Ok(()),
+            Err(Ok(v)) => Err(Error::Io { source: v }),
+            Err(Err(v)) => Err(Error::List { source: v.into() }),
+        }
+    }
+
+    fn snapshot_recursive_program(
+        &self,
+        root: &str,
+        tag: &str,
+        props: &NvList,
+    ) -> Result<(), Error> {
+        let run = || -> io::Result<Option<NvList>> {
+            let mut args = NvList::try_new()?;
+            args.insert("root", root)?;
+            args.insert("tag", tag)?;
+            args.insert("props", &**props)?;
+            let out = self.channel_program(
+                pool_name(root),
+                snapshot::SNAPSHOT_RECURSIVE_PROGRAM,
+                ZCP_DEFAULT_INSTRLIMIT,
+                ZCP_DEFAULT_MEMLIMIT,
+                &args,
+            )?;
+            snapshot::program_errors(&out)
+        };
+        match run() {
+            Ok(None) => Ok(()),
+            Ok(Some(errors)) => Err(Error::List {
+                source: errors.into(),
+            }),
+            Err(source) => Err(Error::Io { source }),
+        }
+    }
+
     /// Create snapshot(s). `snaps` is a list of `bool` (not `boolean_value`) entries, the names of
     /// which correspond to snapshot names.
     ///
@@ -478,6 +677,47 @@ impl Zfs {
         }
     }
 
+    /// Create a set of holds which are released when the returned guard is dropped
+    ///
+    /// This opens a cleanup fd for the holds (see [`HoldGuard`]), so the holds are also released
+    /// if the process dies.
+    ///
+    /// ```no_run
+    /// # let zfs = zfs_core::Zfs::new().unwrap();
+    /// let guard = zfs.hold_temporary(&[("tank/data@backup", "send-job")])?;
+    /// // ... send tank/data@backup, which can't be destroyed meanwhile ...
+    /// drop(guard);
+    /// # Ok::<(), zfs_core::Error>(())
+    /// ```
+    ///
+    /// Corresponds to `lzc_hold`.
+    #[doc(alias = "lzc_hold")]
+    pub fn hold_temporary<'a, H, S, N>(&self, holds: H) -> Result<HoldGuard<'_>, Error>
+    where
+        H: IntoIterator<Item = &'a (S, N)>,
+        S: 'a + CStrArgument + Clone,
+        N: 'a + CStrArgument + Clone,
+    {
+        let cleanup = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(hold::ZFS_DEV)
+            .map_err(|source| Error::Io { source })?;
+
+        let holds: Vec<_> = holds
+            .into_iter()
+            .map(|(s, n)| {
+                (
+                    s.clone().into_cstr().as_ref().to_owned(),
+                    n.clone().into_cstr().as_ref().to_owned(),
+                )
+            })
+            .collect();
+        self.hold(&holds, Some(cleanup.as_raw_fd()))?;
+
+        Ok(HoldGuard::new(self, cleanup, holds))
+    }
+
     /// Release holds from various snapshots
     ///
     /// The holds nvlist is `[(snap_name, [hold_names])]`, allowing multiple holds for multiple
@@ -1062,6 +1302,74 @@ impl Zfs {
         }
     }
 
+    /// Read properties of a dataset, snapshot or bookmark
+    ///
+    /// Runs a read-only channel program (so root privileges are required) which calls
+    /// `zfs.get_prop()` for each of `props`. The entries returned are in the same order as
+    /// `props`.
+    ///
+    /// ```no_run
+    /// use zfs_core::{Prop, PropSource};
+    /// # let zfs = zfs_core::Zfs::new().unwrap();
+    /// let props = zfs.get_props(
+    ///     "tank/data",
+    ///     &[Prop::Used, Prop::Available, Prop::WrittenSince("daily".into())],
+    /// )?;
+    /// for p in &props {
+    ///     println!("{}: {:?} ({:?})", p.prop, p.value, p.source);
+    /// }
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn get_props<S: CStrArgument>(
+        &self,
+        dataset: S,
+        props: &[Prop],
+    ) -> io::Result<Vec<PropEntry>> {
+        let dataset = dataset.into_cstr();
+        let dataset = dataset
+            .as_ref()
+            .to_str()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+        let args = props::get_props_args(dataset, props)?;
+        let out = self.channel_program_nosync(
+            pool_name(dataset),
+            props::GET_PROPS_PROGRAM,
+            ZCP_DEFAULT_INSTRLIMIT,
+            ZCP_DEFAULT_MEMLIMIT,
+            &args,
+        )?;
+        props::decode_props(dataset, props, &out)
+    }
+
+    /// List a dataset and (depending on `opts`) its descendants, snapshots and bookmarks
+    ///
+    /// Like [`get_props()`](Zfs::get_props), this uses read-only channel programs, so root
+    /// privileges are required. Results are fetched in pages as the iterator advances, keeping
+    /// each program within the default instruction and memory limits.
+    ///
+    /// Channel programs can't resume a listing part way through, so each page skips over all the
+    /// entries before it again. The total cost of listing `n` children, snapshots or bookmarks of
+    /// one dataset therefore grows with `n²` (divided by the page size of thousands of entries).
+    ///
+    /// ```no_run
+    /// use zfs_core::{ListOptions, ListType};
+    /// # let zfs = zfs_core::Zfs::new().unwrap();
+    /// let opts = ListOptions {
+    ///     recursive: true,
+    ///     types: vec![ListType::Filesystem, ListType::Snapshot],
+    ///     ..Default::default()
+    /// };
+    /// for e in zfs.list("tank", opts) {
+    ///     let e = e?;
+    ///     println!("{} {}", e.kind, e.name);
+    /// }
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn list<S: Into<String>>(&self, root: S, opts: ListOptions) -> ListIter<'_> {
+        ListIter::new(self, root.into(), opts)
+    }
+
     /// Create a pool checkpoint
     ///
     /// Corresponds to `lzc_pool_checkpoint()`
@@ -1127,6 +1435,11 @@ impl Zfs {
         }
     }
 
+    /// Manage the keys of encrypted datasets, with key derivation and typed commands
+    pub fn key_manager(&self) -> KeyManager<'_> {
+        KeyManager::new(self)
+    }
+
     /// Corresponds to `lzc_unload_key()`
     #[doc(alias = "lzc_unload_key")]
     pub fn unload_key<F: CStrArgument>(&self, fsname: F) -> io::Result<()> {
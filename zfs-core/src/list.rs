@@ -0,0 +1,395 @@
+//! Listing datasets, snapshots and bookmarks with channel programs
+use crate::Zfs;
+use nvpair::{NvData, NvList};
+use std::{fmt, io};
+
+/// The most entries returned by a single run of `LIST_PROGRAM` when each needs `zfs.get_prop()`
+/// calls, and when none do
+const PAGE_SIZE_PROPS: u64 = 2000;
+const PAGE_SIZE_NAMES: u64 = 20000;
+
+/// Lists up to `args.limit` entries of `args.kind` ("children", "snapshots" or "bookmarks") of
+/// `args.dataset`, starting after the entry named `args.after` (if any).
+///
+/// Returns `entries`, a list of name to a list of the entry's `type` and each property named in
+/// `args.props`, and `after` to continue from if there are more entries. The `zfs.list`
+/// iterators can't be resumed, so each page scans (without reading properties) the entries
+/// before it again. If `args.after` no longer exists, returns `lost` instead.
+const LIST_PROGRAM: &str = r#"
+args = ...
+local kind = args["kind"]
+local after = args["after"]
+local found = after == nil
+local entries = {}
+local n = 0
+local last = nil
+local iter
+if kind == "children" then
+    iter = zfs.list.children(args["dataset"])
+elseif kind == "snapshots" then
+    iter = zfs.list.snapshots(args["dataset"])
+else
+    iter = zfs.list.bookmarks(args["dataset"])
+end
+for name in iter do
+    if not found then
+        found = name == after
+    else
+        if n == args["limit"] then
+            return {entries = entries, after = last}
+        end
+        local e = {}
+        if kind == "children" then
+            e["type"] = zfs.get_prop(name, "type")
+        elseif kind == "snapshots" then
+            e["type"] = "snapshot"
+        else
+            e["type"] = "bookmark"
+        end
+        for p, _ in pairs(args["props"]) do
+            e[p] = zfs.get_prop(name, p)
+        end
+        entries[name] = e
+        n = n + 1
+        last = name
+    end
+end
+if not found then
+    return {lost = true}
+end
+return {entries = entries}
+"#;
+
+/// One run of `LIST_PROGRAM`
+#[derive(Debug)]
+pub(crate) struct Page {
+    /// Each entry's name and its `type` and other requested properties
+    pub entries: Vec<(String, NvList)>,
+    /// Where the next page starts, if there is one
+    pub after: Option<String>,
+}
+
+/// Fetch the page of `contents` of `dataset` after `after`, reading `props` of each entry
+pub(crate) fn list_page(
+    zfs: &Zfs,
+    dataset: &str,
+    contents: Contents,
+    after: Option<&str>,
+    props: &[&str],
+) -> io::Result<Page> {
+    let limit = if contents == Contents::Children || !props.is_empty() {
+        PAGE_SIZE_PROPS
+    } else {
+        PAGE_SIZE_NAMES
+    };
+    let mut prop_names = NvList::try_new_unique_names()?;
+    for p in props {
+        prop_names.insert(*p, &true)?;
+    }
+    let mut args = NvList::try_new()?;
+    args.insert("dataset", dataset)?;
+    args.insert("kind", contents.name())?;
+    args.insert("limit", &limit)?;
+    args.insert("props", &*prop_names)?;
+    if let Some(after) = after {
+        args.insert("after", after)?;
+    }
+
+    let out = zfs.channel_program_nosync(
+        crate::pool_name(dataset),
+        LIST_PROGRAM,
+        crate::ZCP_DEFAULT_INSTRLIMIT,
+        crate::ZCP_DEFAULT_MEMLIMIT,
+        &args,
+    )?;
+    let ret = out.lookup_nvlist("return")?;
+
+    if ret.exists("lost") {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!(
+                "{} was removed while listing the {} of {}",
+                after.unwrap_or(""),
+                contents.name(),
+                dataset
+            ),
+        ));
+    }
+
+    let mut entries = Vec::new();
+    for pair in ret.lookup_nvlist("entries")? {
+        let name = pair.name().to_string_lossy().into_owned();
+        let entry = match pair.data() {
+            NvData::NvListRef(l) => l.try_to_owned()?,
+            d => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("unexpected value for {}: {:?}", name, d),
+                ))
+            }
+        };
+        entries.push((name, entry));
+    }
+    let after = match ret.lookup("after") {
+        Ok(pair) => pair.as_str().map(|s| s.to_string_lossy().into_owned()),
+        Err(_) => None,
+    };
+    Ok(Page { entries, after })
+}
+
+/// The kind of an entry returned by [`Zfs::list()`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ListType {
+    Filesystem,
+    Volume,
+    Snapshot,
+    Bookmark,
+}
+
+impl ListType {
+    /// The name used by `zfs list -t` and the `type` property
+    pub fn name(self) -> &'static str {
+        match self {
+            ListType::Filesystem => "filesystem",
+            ListType::Volume => "volume",
+            ListType::Snapshot => "snapshot",
+            ListType::Bookmark => "bookmark",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "filesystem" => ListType::Filesystem,
+            "volume" => ListType::Volume,
+            "snapshot" => ListType::Snapshot,
+            "bookmark" => ListType::Bookmark,
+            _ => return None,
+        })
+    }
+}
+
+impl fmt::Display for ListType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// What [`Zfs::list()`] returns, following `zfs list`
+///
+/// The default lists only the root itself.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ListOptions {
+    /// List all descendants of the root, like `zfs list -r`
+    pub recursive: bool,
+    /// The types of entry to return. When empty, filesystems and volumes are returned.
+    ///
+    /// As with `zfs list -t snapshot <root>`, a non-recursive listing of snapshots or bookmarks
+    /// returns those of the root.
+    pub types: Vec<ListType>,
+    /// Only list descendants up to this depth below the root, like `zfs list -d`. Implies
+    /// `recursive`.
+    pub depth: Option<u32>,
+}
+
+impl ListOptions {
+    fn wants(&self, t: ListType) -> bool {
+        if self.types.is_empty() {
+            matches!(t, ListType::Filesystem | ListType::Volume)
+        } else {
+            self.types.contains(&t)
+        }
+    }
+
+    /// May the contents (of kind `t`) of a dataset at `depth` be listed?
+    fn descend(&self, depth: u32, t: ListType) -> bool {
+        match (self.depth, self.recursive) {
+            (Some(max), _) => depth < max,
+            (None, true) => true,
+            (None, false) => depth == 0 && t != ListType::Filesystem,
+        }
+    }
+}
+
+/// An entry returned by [`Zfs::list()`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListEntry {
+    pub name: String,
+    pub kind: ListType,
+    /// The depth below the root, which is at depth 0. Snapshots and bookmarks are one level
+    /// below their dataset.
+    pub depth: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Contents {
+    Children,
+    Snapshots,
+    Bookmarks,
+}
+
+impl Contents {
+    fn name(self) -> &'static str {
+        match self {
+            Contents::Children => "children",
+            Contents::Snapshots => "snapshots",
+            Contents::Bookmarks => "bookmarks",
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Work {
+    /// Find the type of the root
+    Root(String),
+    /// Return (if wanted) and then descend into an entry
+    Entry(ListEntry),
+    /// Fetch the next page of the contents of a dataset
+    Page {
+        dataset: String,
+        depth: u32,
+        contents: Contents,
+        after: Option<String>,
+    },
+}
+
+/// An iterator over datasets, snapshots and bookmarks, returned by [`Zfs::list()`]
+///
+/// Entries are returned depth first: each dataset is followed by its snapshots, then its
+/// bookmarks, then its children (each followed by their own contents). Entries at the same level
+/// are sorted by name within each page of results.
+///
+/// Each page of results is fetched with a separate read-only channel program, so the listing
+/// isn't atomic. If the entry a page resumes after is destroyed in the meantime, the iterator
+/// returns an error rather than a short listing. Each page also rescans the entries before it, so
+/// listing very many entries of one dataset is quadratic: see [`Zfs::list()`].
+#[derive(Debug)]
+pub struct ListIter<'a> {
+    zfs: &'a Zfs,
+    opts: ListOptions,
+    stack: Vec<Work>,
+}
+
+impl<'a> ListIter<'a> {
+    pub(crate) fn new(zfs: &'a Zfs, root: String, opts: ListOptions) -> Self {
+        ListIter {
+            zfs,
+            opts,
+            stack: vec![Work::Root(root)],
+        }
+    }
+
+    fn push_contents(&mut self, e: &ListEntry) {
+        let mut contents = Vec::new();
+        if e.kind == ListType::Filesystem && self.opts.descend(e.depth, ListType::Filesystem) {
+            contents.push(Contents::Children);
+        }
+        if matches!(e.kind, ListType::Filesystem | ListType::Volume) {
+            if self.opts.wants(ListType::Bookmark) && self.opts.descend(e.depth, ListType::Bookmark)
+            {
+                contents.push(Contents::Bookmarks);
+            }
+            if self.opts.wants(ListType::Snapshot) && self.opts.descend(e.depth, ListType::Snapshot)
+            {
+                contents.push(Contents::Snapshots);
+            }
+        }
+        for c in contents {
+            self.stack.push(Work::Page {
+                dataset: e.name.clone(),
+                depth: e.depth + 1,
+                contents: c,
+                after: None,
+            });
+        }
+    }
+
+    fn root(&self, root: String) -> io::Result<ListEntry> {
+        let t = self
+            .zfs
+            .get_props(&*root, &[crate::Prop::Type])?
+            .pop()
+            .and_then(|p| p.value)
+            .and_then(|v| v.as_str().and_then(ListType::from_name))
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("unknown type for {}", root),
+                )
+            })?;
+        Ok(ListEntry {
+            name: root,
+            kind: t,
+            depth: 0,
+        })
+    }
+
+    fn page(
+        &mut self,
+        dataset: String,
+        depth: u32,
+        contents: Contents,
+        after: Option<String>,
+    ) -> io::Result<()> {
+        let page = list_page(self.zfs, &dataset, contents, after.as_deref(), &[])?;
+
+        let mut entries = Vec::new();
+        for (name, entry) in page.entries {
+            let kind = entry
+                .lookup_string("type")
+                .ok()
+                .and_then(|t| ListType::from_name(&t.to_string_lossy()))
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("unknown type for {}", name),
+                    )
+                })?;
+            entries.push(ListEntry { name, kind, depth });
+        }
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+
+        if let Some(after) = page.after {
+            self.stack.push(Work::Page {
+                dataset,
+                depth,
+                contents,
+                after: Some(after),
+            });
+        }
+        self.stack
+            .extend(entries.into_iter().rev().map(Work::Entry));
+        Ok(())
+    }
+}
+
+impl<'a> Iterator for ListIter<'a> {
+    type Item = io::Result<ListEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let r = match self.stack.pop()? {
+                Work::Root(root) => self.root(root).map(|e| {
+                    self.stack.push(Work::Entry(e));
+                }),
+                Work::Entry(e) => {
+                    self.push_contents(&e);
+                    if self.opts.wants(e.kind) {
+                        return Some(Ok(e));
+                    }
+                    Ok(())
+                }
+                Work::Page {
+                    dataset,
+                    depth,
+                    contents,
+                    after,
+                } => self.page(dataset, depth, contents, after),
+            };
+            if let Err(e) = r {
+                // don't continue with a partial listing
+                self.stack.clear();
+                return Some(Err(e));
+            }
+        }
+    }
+}
@@ -0,0 +1,836 @@
+//! Typed dataset properties
+//!
+//! [`DatasetProp`] names the native properties (`zfs_prop_t`) which can be set when creating a
+//! dataset, and [`DatasetProps`] builds the nvlist that `lzc_create()` expects from typed values.
+//! The kernel takes index properties (like `compression` or `atime`) as their numeric value,
+//! numeric properties as a `uint64`, and everything else as a string. Values are range checked,
+//! and properties which don't apply to the type of dataset being created are rejected, much as
+//! `zfs create` would.
+use crate::DataSetType;
+use nvpair::{NvData, NvEncode, NvList, NvListRef};
+use std::borrow::Cow;
+use std::{fmt, io};
+use zfs_core_sys as sys;
+
+const SPA_MINBLOCKSIZE: u64 = 512;
+const SPA_MAXBLOCKSIZE: u64 = 16 * 1024 * 1024;
+const SPA_OLD_MAXBLOCKSIZE: u64 = 128 * 1024;
+const SPECIAL_SMALL_BLOCKS_MAX: u64 = 1024 * 1024;
+
+/// Index properties (stored as a number, named by a string) with a fixed set of values
+macro_rules! index_prop {
+    (
+        $(#[$m:meta])*
+        $name:ident {
+            $($(#[$vm:meta])* $variant:ident = $value:expr, $s:expr;)*
+        }
+    ) => {
+        $(#[$m])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum $name {
+            $($(#[$vm])* $variant,)*
+        }
+
+        impl $name {
+            /// The value stored for this property
+            pub fn as_raw(&self) -> u64 {
+                match self {
+                    $($name::$variant => $value as u64,)*
+                }
+            }
+
+            pub fn from_raw(v: u64) -> Option<Self> {
+                $(if v == $value as u64 {
+                    return Some($name::$variant);
+                })*
+                None
+            }
+
+            /// The name of this value, as used by `zfs get` and `zfs set`
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $($name::$variant => $s,)*
+                }
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(self.name())
+            }
+        }
+    };
+}
+
+index_prop! {
+    /// `canmount`
+    CanMount {
+        Off = sys::zfs_canmount_type_t::ZFS_CANMOUNT_OFF, "off";
+        On = sys::zfs_canmount_type_t::ZFS_CANMOUNT_ON, "on";
+        NoAuto = sys::zfs_canmount_type_t::ZFS_CANMOUNT_NOAUTO, "noauto";
+    }
+}
+
+index_prop! {
+    /// `logbias`
+    LogBias {
+        Latency = sys::zfs_logbias_op_t::ZFS_LOGBIAS_LATENCY, "latency";
+        Throughput = sys::zfs_logbias_op_t::ZFS_LOGBIAS_THROUGHPUT, "throughput";
+    }
+}
+
+index_prop! {
+    /// `primarycache` and `secondarycache`
+    Cache {
+        None = sys::zfs_cache_type::ZFS_CACHE_NONE, "none";
+        Metadata = sys::zfs_cache_type::ZFS_CACHE_METADATA, "metadata";
+        All = sys::zfs_cache_type::ZFS_CACHE_ALL, "all";
+    }
+}
+
+index_prop! {
+    /// `sync`
+    Sync {
+        Standard = sys::zfs_sync_type_t::ZFS_SYNC_STANDARD, "standard";
+        Always = sys::zfs_sync_type_t::ZFS_SYNC_ALWAYS, "always";
+        Disabled = sys::zfs_sync_type_t::ZFS_SYNC_DISABLED, "disabled";
+    }
+}
+
+index_prop! {
+    /// `xattr`
+    Xattr {
+        Off = sys::zfs_xattr_type_t::ZFS_XATTR_OFF, "off";
+        /// Directory based extended attributes
+        Dir = sys::zfs_xattr_type_t::ZFS_XATTR_DIR, "dir";
+        /// System attribute based extended attributes
+        Sa = sys::zfs_xattr_type_t::ZFS_XATTR_SA, "sa";
+    }
+}
+
+index_prop! {
+    /// `dnodesize`
+    DnodeSize {
+        Legacy = sys::zfs_dnsize_type_t::ZFS_DNSIZE_LEGACY, "legacy";
+        Auto = sys::zfs_dnsize_type_t::ZFS_DNSIZE_AUTO, "auto";
+        K1 = sys::zfs_dnsize_type_t::ZFS_DNSIZE_1K, "1k";
+        K2 = sys::zfs_dnsize_type_t::ZFS_DNSIZE_2K, "2k";
+        K4 = sys::zfs_dnsize_type_t::ZFS_DNSIZE_4K, "4k";
+        K8 = sys::zfs_dnsize_type_t::ZFS_DNSIZE_8K, "8k";
+        K16 = sys::zfs_dnsize_type_t::ZFS_DNSIZE_16K, "16k";
+    }
+}
+
+index_prop! {
+    /// `redundant_metadata`
+    RedundantMetadata {
+        All = sys::zfs_redundant_metadata_type_t::ZFS_REDUNDANT_METADATA_ALL, "all";
+        Most = sys::zfs_redundant_metadata_type_t::ZFS_REDUNDANT_METADATA_MOST, "most";
+    }
+}
+
+index_prop! {
+    /// `volmode`
+    VolMode {
+        Default = sys::zfs_volmode_t::ZFS_VOLMODE_DEFAULT, "default";
+        Geom = sys::zfs_volmode_t::ZFS_VOLMODE_GEOM, "geom";
+        Dev = sys::zfs_volmode_t::ZFS_VOLMODE_DEV, "dev";
+        None = sys::zfs_volmode_t::ZFS_VOLMODE_NONE, "none";
+    }
+}
+
+index_prop! {
+    /// `snapdir`
+    SnapDir {
+        Hidden = 0, "hidden";
+        Visible = 1, "visible";
+    }
+}
+
+index_prop! {
+    /// `checksum` (`enum zio_checksum`)
+    Checksum {
+        On = 1, "on";
+        Off = 2, "off";
+        Fletcher2 = 6, "fletcher2";
+        Fletcher4 = 7, "fletcher4";
+        Sha256 = 8, "sha256";
+        NoParity = 10, "noparity";
+        Sha512 = 11, "sha512";
+        Skein = 12, "skein";
+        Edonr = 13, "edonr";
+    }
+}
+
+// `enum zio_compress`
+const ZIO_COMPRESS_ON: u64 = 1;
+const ZIO_COMPRESS_OFF: u64 = 2;
+const ZIO_COMPRESS_LZJB: u64 = 3;
+const ZIO_COMPRESS_GZIP_1: u64 = 5;
+const ZIO_COMPRESS_ZLE: u64 = 14;
+const ZIO_COMPRESS_LZ4: u64 = 15;
+const ZIO_COMPRESS_ZSTD: u64 = 16;
+/// The zstd level is stored above the compression function
+const SPA_COMPRESSBITS: u64 = 7;
+/// `ZIO_ZSTD_LEVEL_FAST_1`: negative zstd levels are numbered from here, after
+/// `ZIO_ZSTD_LEVEL_RESERVE` (101) and `ZIO_ZSTD_LEVEL_FAST` (102)
+const ZIO_ZSTD_LEVEL_FAST_1: u64 = 103;
+
+/// `compression`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Compression {
+    Off,
+    /// The pool's default algorithm
+    On,
+    Lzjb,
+    /// Levels 1 to 9
+    Gzip(u8),
+    Zle,
+    Lz4,
+    /// Levels 1 to 19
+    Zstd(u8),
+    /// zstd's negative levels: 1 to 10, 20 to 100 in steps of 10, 500 and 1000
+    ZstdFast(u16),
+}
+
+impl Compression {
+    /// The value stored for this property, or `None` if the level is out of range
+    pub fn as_raw(&self) -> Option<u64> {
+        Some(match *self {
+            Compression::Off => ZIO_COMPRESS_OFF,
+            Compression::On => ZIO_COMPRESS_ON,
+            Compression::Lzjb => ZIO_COMPRESS_LZJB,
+            Compression::Gzip(l @ 1..=9) => ZIO_COMPRESS_GZIP_1 + u64::from(l) - 1,
+            Compression::Zle => ZIO_COMPRESS_ZLE,
+            Compression::Lz4 => ZIO_COMPRESS_LZ4,
+            Compression::Zstd(l @ 1..=19) => ZIO_COMPRESS_ZSTD | u64::from(l) << SPA_COMPRESSBITS,
+            Compression::ZstdFast(l) => {
+                let level = match l {
+                    1..=10 => ZIO_ZSTD_LEVEL_FAST_1 + u64::from(l) - 1,
+                    20..=100 if l % 10 == 0 => ZIO_ZSTD_LEVEL_FAST_1 + 9 + u64::from(l) / 10 - 1,
+                    500 => ZIO_ZSTD_LEVEL_FAST_1 + 19,
+                    1000 => ZIO_ZSTD_LEVEL_FAST_1 + 20,
+                    _ => return None,
+                };
+                ZIO_COMPRESS_ZSTD | level << SPA_COMPRESSBITS
+            }
+            _ => return None,
+        })
+    }
+}
+
+impl fmt::Display for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Compression::Off => f.write_str("off"),
+            Compression::On => f.write_str("on"),
+            Compression::Lzjb => f.write_str("lzjb"),
+            Compression::Gzip(l) => write!(f, "gzip-{}", l),
+            Compression::Zle => f.write_str("zle"),
+            Compression::Lz4 => f.write_str("lz4"),
+            Compression::Zstd(l) => write!(f, "zstd-{}", l),
+            Compression::ZstdFast(l) => write!(f, "zstd-fast-{}", l),
+        }
+    }
+}
+
+/// Which kinds of dataset a property applies to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Applies {
+    Filesystem,
+    Volume,
+    Both,
+}
+
+macro_rules! dataset_props {
+    ($($(#[$m:meta])* $variant:ident = $raw:ident, $name:expr, $applies:ident;)*) => {
+        /// A native dataset property which may be set when creating a dataset (`zfs_prop_t`)
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum DatasetProp {
+            $($(#[$m])* $variant,)*
+        }
+
+        impl DatasetProp {
+            pub fn as_raw(&self) -> sys::zfs_prop_t::Type {
+                match self {
+                    $(DatasetProp::$variant => sys::zfs_prop_t::$raw,)*
+                }
+            }
+
+            pub fn from_raw(v: sys::zfs_prop_t::Type) -> Option<Self> {
+                match v {
+                    $(sys::zfs_prop_t::$raw => Some(DatasetProp::$variant),)*
+                    _ => None,
+                }
+            }
+
+            /// The property's name, as used in nvlists and by `zfs get`
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $(DatasetProp::$variant => $name,)*
+                }
+            }
+
+            fn applies(&self) -> Applies {
+                match self {
+                    $(DatasetProp::$variant => Applies::$applies,)*
+                }
+            }
+        }
+    };
+}
+
+dataset_props! {
+    Atime = ZFS_PROP_ATIME, "atime", Filesystem;
+    CanMount = ZFS_PROP_CANMOUNT, "canmount", Filesystem;
+    Checksum = ZFS_PROP_CHECKSUM, "checksum", Both;
+    Compression = ZFS_PROP_COMPRESSION, "compression", Both;
+    Copies = ZFS_PROP_COPIES, "copies", Both;
+    Devices = ZFS_PROP_DEVICES, "devices", Filesystem;
+    DnodeSize = ZFS_PROP_DNODESIZE, "dnodesize", Filesystem;
+    Exec = ZFS_PROP_EXEC, "exec", Filesystem;
+    LogBias = ZFS_PROP_LOGBIAS, "logbias", Both;
+    Mountpoint = ZFS_PROP_MOUNTPOINT, "mountpoint", Filesystem;
+    PrimaryCache = ZFS_PROP_PRIMARYCACHE, "primarycache", Both;
+    Quota = ZFS_PROP_QUOTA, "quota", Filesystem;
+    Readonly = ZFS_PROP_READONLY, "readonly", Both;
+    Recordsize = ZFS_PROP_RECORDSIZE, "recordsize", Filesystem;
+    RedundantMetadata = ZFS_PROP_REDUNDANT_METADATA, "redundant_metadata", Both;
+    RefQuota = ZFS_PROP_REFQUOTA, "refquota", Filesystem;
+    RefReservation = ZFS_PROP_REFRESERVATION, "refreservation", Both;
+    Relatime = ZFS_PROP_RELATIME, "relatime", Filesystem;
+    Reservation = ZFS_PROP_RESERVATION, "reservation", Both;
+    SecondaryCache = ZFS_PROP_SECONDARYCACHE, "secondarycache", Both;
+    Setuid = ZFS_PROP_SETUID, "setuid", Filesystem;
+    SnapDir = ZFS_PROP_SNAPDIR, "snapdir", Filesystem;
+    SpecialSmallBlocks = ZFS_PROP_SPECIAL_SMALL_BLOCKS, "special_small_blocks", Filesystem;
+    Sync = ZFS_PROP_SYNC, "sync", Both;
+    VolBlockSize = ZFS_PROP_VOLBLOCKSIZE, "volblocksize", Volume;
+    VolMode = ZFS_PROP_VOLMODE, "volmode", Volume;
+    VolSize = ZFS_PROP_VOLSIZE, "volsize", Volume;
+    Xattr = ZFS_PROP_XATTR, "xattr", Filesystem;
+}
+
+impl DatasetProp {
+    /// Whether the property can be set on a dataset of type `t`
+    pub fn applies_to(&self, t: DataSetType) -> bool {
+        matches!(
+            (self.applies(), t),
+            (Applies::Both, _)
+                | (Applies::Filesystem, DataSetType::Zfs)
+                | (Applies::Volume, DataSetType::Zvol)
+        )
+    }
+}
+
+impl fmt::Display for DatasetProp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+fn is_block_size(v: u64, max: u64) -> bool {
+    v.is_power_of_two() && v >= SPA_MINBLOCKSIZE && v <= max
+}
+
+/// Properties for a new dataset, for [`Zfs::create_with_props()`](crate::Zfs::create_with_props)
+///
+/// Setters may be chained. The first invalid value (or property which doesn't apply to the
+/// dataset type) is reported by [`build()`](Self::build).
+///
+/// ```no_run
+/// use zfs_core::{Compression, DataSetType, DatasetProps};
+/// let props = DatasetProps::new(DataSetType::Zfs)
+///     .compression(Compression::Zstd(3))
+///     .recordsize(128 * 1024)
+///     .atime(false)
+///     .user("com.example:owner", "alice");
+/// # let zfs = zfs_core::Zfs::new().unwrap();
+/// zfs.create_with_props("tank/data", props)?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct DatasetProps {
+    dataset_type: DataSetType,
+    nv: NvList,
+    error: Option<io::Error>,
+}
+
+impl DatasetProps {
+    pub fn new(dataset_type: DataSetType) -> Self {
+        DatasetProps {
+            dataset_type,
+            nv: NvList::new_unique_names(),
+            error: None,
+        }
+    }
+
+    pub fn dataset_type(&self) -> DataSetType {
+        self.dataset_type
+    }
+
+    fn fail(mut self, prop: &str, msg: &str) -> Self {
+        if self.error.is_none() {
+            self.error = Some(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{}: {}", prop, msg),
+            ));
+        }
+        self
+    }
+
+    /// Set `prop` to `value`, if it applies to our dataset type
+    fn set<V: NvEncode + ?Sized>(mut self, prop: DatasetProp, value: &V) -> Self {
+        if !prop.applies_to(self.dataset_type) {
+            let msg = match self.dataset_type {
+                DataSetType::Zfs => "only applies to volumes",
+                DataSetType::Zvol => "only applies to filesystems",
+            };
+            return self.fail(prop.name(), msg);
+        }
+        match self.nv.insert(prop.name(), value) {
+            Ok(()) => self,
+            Err(e) => {
+                if self.error.is_none() {
+                    self.error = Some(e.into());
+                }
+                self
+            }
+        }
+    }
+
+    fn set_bool(self, prop: DatasetProp, on: bool) -> Self {
+        self.set(prop, &u64::from(on))
+    }
+
+    pub fn atime(self, on: bool) -> Self {
+        self.set_bool(DatasetProp::Atime, on)
+    }
+
+    pub fn canmount(self, v: CanMount) -> Self {
+        self.set(DatasetProp::CanMount, &v.as_raw())
+    }
+
+    pub fn checksum(self, v: Checksum) -> Self {
+        self.set(DatasetProp::Checksum, &v.as_raw())
+    }
+
+    pub fn compression(self, v: Compression) -> Self {
+        match v.as_raw() {
+            Some(raw) => self.set(DatasetProp::Compression, &raw),
+            None => self.fail("compression", &format!("invalid level for {}", v)),
+        }
+    }
+
+    /// Number of copies of data to store, from 1 to 3
+    pub fn copies(self, n: u64) -> Self {
+        if !(1..=3).contains(&n) {
+            return self.fail("copies", "must be 1, 2 or 3");
+        }
+        self.set(DatasetProp::Copies, &n)
+    }
+
+    pub fn devices(self, on: bool) -> Self {
+        self.set_bool(DatasetProp::Devices, on)
+    }
+
+    pub fn dnodesize(self, v: DnodeSize) -> Self {
+        self.set(DatasetProp::DnodeSize, &v.as_raw())
+    }
+
+    pub fn exec(self, on: bool) -> Self {
+        self.set_bool(DatasetProp::Exec, on)
+    }
+
+    pub fn logbias(self, v: LogBias) -> Self {
+        self.set(DatasetProp::LogBias, &v.as_raw())
+    }
+
+    /// An absolute path, `none` or `legacy`
+    pub fn mountpoint(self, path: &str) -> Self {
+        if !(path.starts_with('/') || path == "none" || path == "legacy") {
+            return self.fail("mountpoint", "must be an absolute path, 'none' or 'legacy'");
+        }
+        self.set(DatasetProp::Mountpoint, path)
+    }
+
+    pub fn primarycache(self, v: Cache) -> Self {
+        self.set(DatasetProp::PrimaryCache, &v.as_raw())
+    }
+
+    /// Limit on space used by the dataset and its descendents, in bytes. `0` means no quota.
+    pub fn quota(self, bytes: u64) -> Self {
+        self.set(DatasetProp::Quota, &bytes)
+    }
+
+    pub fn readonly(self, on: bool) -> Self {
+        self.set_bool(DatasetProp::Readonly, on)
+    }
+
+    /// A power of 2 from 512 bytes to 16 MiB (sizes over 128 KiB need the `large_blocks`
+    /// feature)
+    pub fn recordsize(self, bytes: u64) -> Self {
+        if !is_block_size(bytes, SPA_MAXBLOCKSIZE) {
+            return self.fail("recordsize", "must be a power of 2 from 512B to 16M");
+        }
+        self.set(DatasetProp::Recordsize, &bytes)
+    }
+
+    pub fn redundant_metadata(self, v: RedundantMetadata) -> Self {
+        self.set(DatasetProp::RedundantMetadata, &v.as_raw())
+    }
+
+    /// Limit on space referenced by the dataset, in bytes. `0` means no quota.
+    pub fn refquota(self, bytes: u64) -> Self {
+        self.set(DatasetProp::RefQuota, &bytes)
+    }
+
+    pub fn refreservation(self, bytes: u64) -> Self {
+        self.set(DatasetProp::RefReservation, &bytes)
+    }
+
+    pub fn relatime(self, on: bool) -> Self {
+        self.set_bool(DatasetProp::Relatime, on)
+    }
+
+    pub fn reservation(self, bytes: u64) -> Self {
+        self.set(DatasetProp::Reservation, &bytes)
+    }
+
+    pub fn secondarycache(self, v: Cache) -> Self {
+        self.set(DatasetProp::SecondaryCache, &v.as_raw())
+    }
+
+    pub fn setuid(self, on: bool) -> Self {
+        self.set_bool(DatasetProp::Setuid, on)
+    }
+
+    pub fn snapdir(self, v: SnapDir) -> Self {
+        self.set(DatasetProp::SnapDir, &v.as_raw())
+    }
+
+    /// `0`, or a power of 2 from 512 bytes to 1 MiB
+    pub fn special_small_blocks(self, bytes: u64) -> Self {
+        if bytes != 0 && !is_block_size(bytes, SPECIAL_SMALL_BLOCKS_MAX) {
+            return self.fail(
+                "special_small_blocks",
+                "must be 0 or a power of 2 from 512B to 1M",
+            );
+        }
+        self.set(DatasetProp::SpecialSmallBlocks, &bytes)
+    }
+
+    pub fn sync(self, v: Sync) -> Self {
+        self.set(DatasetProp::Sync, &v.as_raw())
+    }
+
+    /// A power of 2 from 512 bytes to 128 KiB. Can only be set at creation.
+    pub fn volblocksize(self, bytes: u64) -> Self {
+        if !is_block_size(bytes, SPA_OLD_MAXBLOCKSIZE) {
+            return self.fail("volblocksize", "must be a power of 2 from 512B to 128K");
+        }
+        self.set(DatasetProp::VolBlockSize, &bytes)
+    }
+
+    pub fn volmode(self, v: VolMode) -> Self {
+        self.set(DatasetProp::VolMode, &v.as_raw())
+    }
+
+    /// The size of a volume in bytes, which is required when creating one. Must be a multiple of
+    /// `volblocksize`.
+    pub fn volsize(self, bytes: u64) -> Self {
+        if bytes == 0 {
+            return self.fail("volsize", "must be greater than 0");
+        }
+        self.set(DatasetProp::VolSize, &bytes)
+    }
+
+    pub fn xattr(self, v: Xattr) -> Self {
+        self.set(DatasetProp::Xattr, &v.as_raw())
+    }
+
+    /// A user property. `name` must contain a `:`, as in `com.example:owner`.
+    pub fn user(mut self, name: &str, value: &str) -> Self {
+        if let Err(msg) = check_user_prop(name, value) {
+            return self.fail(name, msg);
+        }
+        if let Err(e) = self.nv.insert(name, value) {
+            if self.error.is_none() {
+                self.error = Some(e.into());
+            }
+        }
+        self
+    }
+
+    /// Check the properties as a whole, and return the nvlist to pass to `lzc_create()`
+    pub fn build(self) -> io::Result<NvList> {
+        if let Some(e) = self.error {
+            return Err(e);
+        }
+        if let DataSetType::Zvol = self.dataset_type {
+            let size = self.nv.lookup_uint64("volsize").map_err(|_| {
+                io::Error::new(io::ErrorKind::InvalidInput, "volsize: required for volumes")
+            })?;
+            if let Ok(bs) = self.nv.lookup_uint64("volblocksize") {
+                if size % bs != 0 {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "volsize: must be a multiple of volblocksize",
+                    ));
+                }
+            }
+        }
+        Ok(self.nv)
+    }
+}
+
+/// Check a user property against the rules of `zfs_prop_user()` and the ZAP size limits
+pub(crate) fn check_user_prop(name: &str, value: &str) -> Result<(), &'static str> {
+    // ZAP_MAXNAMELEN and ZAP_MAXVALUELEN, both including the nul
+    if name.len() >= 256 {
+        return Err("user property names must be shorter than 256 bytes");
+    }
+    if value.len() >= 8192 {
+        return Err("user property values must be shorter than 8192 bytes");
+    }
+    if !name.bytes().all(|c| {
+        c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, b'-' | b'_' | b'.' | b':')
+    }) {
+        return Err(
+            "user property names may only contain lowercase letters, digits, '-', '_', '.' and ':'",
+        );
+    }
+    if !name.contains(':') {
+        return Err("user property names must contain a ':'");
+    }
+    Ok(())
+}
+
+/// A property to read with [`Zfs::get_props()`](crate::Zfs::get_props)
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Prop {
+    /// A property which may also be set
+    Settable(DatasetProp),
+    Type,
+    Creation,
+    Used,
+    Available,
+    Referenced,
+    CompressRatio,
+    RefCompressRatio,
+    Mounted,
+    Origin,
+    CreateTxg,
+    Guid,
+    UsedBySnapshots,
+    UsedByDataset,
+    UsedByChildren,
+    UsedByRefReservation,
+    UserRefs,
+    /// Space written since the previous snapshot
+    Written,
+    LogicalUsed,
+    LogicalReferenced,
+    Encryption,
+    KeyFormat,
+    KeyLocation,
+    Pbkdf2Salt,
+    Pbkdf2Iters,
+    EncryptionRoot,
+    KeyStatus,
+    ReceiveResumeToken,
+    /// `written@snap`: space written since the snapshot (or bookmark, as `#bookmark`) named
+    /// `snap`, which is given without the dataset name
+    WrittenSince(String),
+    /// A user property, such as `com.example:owner`
+    User(String),
+}
+
+impl Prop {
+    /// The property's name, as passed to `zfs.get_prop()`
+    pub fn name(&self) -> Cow<'_, str> {
+        Cow::Borrowed(match self {
+            Prop::Settable(p) => p.name(),
+            Prop::Type => "type",
+            Prop::Creation => "creation",
+            Prop::Used => "used",
+            Prop::Available => "available",
+            Prop::Referenced => "referenced",
+            Prop::CompressRatio => "compressratio",
+            Prop::RefCompressRatio => "refcompressratio",
+            Prop::Mounted => "mounted",
+            Prop::Origin => "origin",
+            Prop::CreateTxg => "createtxg",
+            Prop::Guid => "guid",
+            Prop::UsedBySnapshots => "usedbysnapshots",
+            Prop::UsedByDataset => "usedbydataset",
+            Prop::UsedByChildren => "usedbychildren",
+            Prop::UsedByRefReservation => "usedbyrefreservation",
+            Prop::UserRefs => "userrefs",
+            Prop::Written => "written",
+            Prop::LogicalUsed => "logicalused",
+            Prop::LogicalReferenced => "logicalreferenced",
+            Prop::Encryption => "encryption",
+            Prop::KeyFormat => "keyformat",
+            Prop::KeyLocation => "keylocation",
+            Prop::Pbkdf2Salt => "pbkdf2salt",
+            Prop::Pbkdf2Iters => "pbkdf2iters",
+            Prop::EncryptionRoot => "encryptionroot",
+            Prop::KeyStatus => "keystatus",
+            Prop::ReceiveResumeToken => "receive_resume_token",
+            Prop::WrittenSince(snap) => {
+                let snap = snap.trim_start_matches('@');
+                return Cow::Owned(format!("written@{}", snap));
+            }
+            Prop::User(name) => name,
+        })
+    }
+}
+
+impl From<DatasetProp> for Prop {
+    fn from(p: DatasetProp) -> Self {
+        Prop::Settable(p)
+    }
+}
+
+impl fmt::Display for Prop {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.name())
+    }
+}
+
+/// The value of a property, as returned by `zfs.get_prop()`
+///
+/// Numeric properties are numbers. Index properties (like `compression`) are the name of their
+/// value, as `zfs get` shows it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PropValue {
+    Number(u64),
+    String(String),
+}
+
+impl PropValue {
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            PropValue::Number(v) => Some(*v),
+            PropValue::String(_) => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            PropValue::String(v) => Some(v),
+            PropValue::Number(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for PropValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PropValue::Number(v) => write!(f, "{}", v),
+            PropValue::String(v) => f.write_str(v),
+        }
+    }
+}
+
+/// Where a property's value comes from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PropSource {
+    /// Set on the dataset itself
+    Local,
+    /// Inherited from the named ancestor
+    Inherited(String),
+    Default,
+    /// Set by `zfs receive`
+    Received,
+    /// Read-only properties, which have no source
+    None,
+}
+
+impl PropSource {
+    /// Decode the source returned by `zfs.get_prop()` for `dataset`, which is the name of the
+    /// dataset the value was set on, or a special value
+    fn decode(dataset: &str, source: Option<&str>) -> Self {
+        match source {
+            None | Some("") | Some("-") | Some("none") => PropSource::None,
+            Some("default") => PropSource::Default,
+            Some("$recvd") | Some("received") => PropSource::Received,
+            Some(s) if s == dataset => PropSource::Local,
+            Some(s) => PropSource::Inherited(s.to_owned()),
+        }
+    }
+}
+
+/// A property read by [`Zfs::get_props()`](crate::Zfs::get_props)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PropEntry {
+    pub prop: Prop,
+    /// `None` if the property has no value (such as an unset user property)
+    pub value: Option<PropValue>,
+    pub source: PropSource,
+}
+
+/// Reads each property named in `args.props` (a list of names with boolean values) of
+/// `args.dataset`
+pub(crate) const GET_PROPS_PROGRAM: &str = r#"
+args = ...
+local out = {}
+for p, _ in pairs(args["props"]) do
+    local value, source = zfs.get_prop(args["dataset"], p)
+    out[p] = {value = value, source = source}
+end
+return out
+"#;
+
+pub(crate) fn get_props_args(dataset: &str, props: &[Prop]) -> io::Result<NvList> {
+    let mut names = NvList::try_new_unique_names()?;
+    for p in props {
+        names.insert(&*p.name(), &true)?;
+    }
+    let mut args = NvList::try_new()?;
+    args.insert("dataset", dataset)?;
+    args.insert("props", &*names)?;
+    Ok(args)
+}
+
+/// Decode the output of `GET_PROPS_PROGRAM` into entries in the order of `props`
+pub(crate) fn decode_props(
+    dataset: &str,
+    props: &[Prop],
+    out: &NvListRef,
+) -> io::Result<Vec<PropEntry>> {
+    let ret = out.lookup_nvlist("return")?;
+    props
+        .iter()
+        .map(|p| {
+            let name = p.name();
+            let entry = ret.lookup_nvlist(&*name)?;
+            let value = match entry.lookup("value") {
+                Err(_) => None,
+                Ok(pair) => Some(match pair.data() {
+                    // lua numbers are signed, but large values (like guids) are really unsigned
+                    NvData::Int64(v) => PropValue::Number(v as u64),
+                    NvData::Uint64(v) => PropValue::Number(v),
+                    NvData::Str(v) => PropValue::String(v.to_string_lossy().into_owned()),
+                    d => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!("unexpected value for {}: {:?}", name, d),
+                        ))
+                    }
+                }),
+            };
+            let source = entry
+                .lookup("source")
+                .ok()
+                .and_then(|s| s.as_str())
+                .map(|s| s.to_string_lossy());
+            Ok(PropEntry {
+                prop: p.clone(),
+                value,
+                source: PropSource::decode(dataset, source.as_deref()),
+            })
+        })
+        .collect()
+}
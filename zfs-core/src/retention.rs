@@ -0,0 +1,389 @@
+//! Snapshot retention policies
+//!
+//! A [`RetentionPolicy`] decides which snapshots of a dataset to keep, producing a
+//! [`RetentionPlan`] which may be inspected (a dry run) before it is executed.
+use crate::list::{list_page, Contents};
+use crate::{Defer, Zfs};
+use nvpair::{NvData, NvList, NvListRef};
+use std::collections::BTreeSet;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::{fmt, io};
+
+/// The most snapshots destroyed by a single `lzc_destroy_snaps()` call
+const DESTROY_BATCH: usize = 100;
+
+/// A count of periods to keep, and the period containing a timestamp
+type Rule = (u32, KeepReason, fn(u64) -> i64);
+
+/// Which snapshots of a dataset to keep
+///
+/// Each of `hourly`, `daily`, `weekly` and `monthly` keeps the newest snapshot in each of that
+/// many of the most recent periods (of UTC time, with weeks starting on Monday) which have a
+/// snapshot. A snapshot may be kept by several rules. Snapshots not kept by any rule are
+/// destroyed, so the default policy (which keeps nothing) destroys every snapshot it manages.
+///
+/// ```no_run
+/// use std::time::{Duration, SystemTime};
+/// use zfs_core::RetentionPolicy;
+/// # let zfs = zfs_core::Zfs::new().unwrap();
+/// let policy = RetentionPolicy {
+///     hourly: 24,
+///     daily: 7,
+///     keep_within: Some(Duration::from_secs(2 * 60 * 60)),
+///     honour_holds: true,
+///     prefix: Some("auto-".to_owned()),
+///     ..Default::default()
+/// };
+/// let plan = policy.plan(&zfs, "tank/data", SystemTime::now())?;
+/// print!("{}", plan);
+/// plan.execute(&zfs).map_err(|(e, _)| e)?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    pub hourly: u32,
+    pub daily: u32,
+    pub weekly: u32,
+    pub monthly: u32,
+    /// Keep every snapshot newer than this
+    pub keep_within: Option<Duration>,
+    /// Keep snapshots which have holds. Otherwise they are destroyed with [`Defer::Yes`], and
+    /// disappear once their holds are released.
+    pub honour_holds: bool,
+    /// Only manage snapshots whose name (after the `@`) starts with this
+    pub prefix: Option<String>,
+    /// Take the time of each snapshot from its name (after the `@`) instead of its `creation`
+    /// property, parsing it (as UTC) with this format.
+    ///
+    /// The format must match the whole name. `%Y` is a 4 digit year, `%m`, `%d`, `%H`, `%M`
+    /// and `%S` are 2 digit month, day, hour, minute and second, and `%%` is a `%`. Any other
+    /// character matches itself. For example, `auto-%Y-%m-%d_%H%M`. Snapshots which don't match
+    /// aren't managed.
+    pub name_format: Option<String>,
+}
+
+/// A snapshot considered by a [`RetentionPolicy`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotInfo {
+    /// The full snapshot name, `dataset@tag`
+    pub name: String,
+    /// Seconds since the unix epoch
+    pub timestamp: u64,
+    /// The snapshot has user holds
+    pub held: bool,
+}
+
+/// Why a [`RetentionPolicy`] keeps a snapshot
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeepReason {
+    Hourly,
+    Daily,
+    Weekly,
+    Monthly,
+    Within,
+    Held,
+}
+
+impl fmt::Display for KeepReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            KeepReason::Hourly => "hourly",
+            KeepReason::Daily => "daily",
+            KeepReason::Weekly => "weekly",
+            KeepReason::Monthly => "monthly",
+            KeepReason::Within => "within",
+            KeepReason::Held => "held",
+        })
+    }
+}
+
+/// A snapshot kept by a [`RetentionPlan`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Kept {
+    pub snapshot: SnapshotInfo,
+    pub reasons: Vec<KeepReason>,
+}
+
+/// The result of applying a [`RetentionPolicy`], newest snapshots first
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RetentionPlan {
+    pub keep: Vec<Kept>,
+    pub destroy: Vec<SnapshotInfo>,
+    /// Snapshots which the policy doesn't manage, because of its `prefix` or `name_format`
+    pub ignored: Vec<String>,
+}
+
+impl RetentionPolicy {
+    /// Decide which of `snaps` to keep, relative to the time `now`
+    ///
+    /// `prefix` and `name_format` aren't applied: all of `snaps` are managed.
+    pub fn select(&self, mut snaps: Vec<SnapshotInfo>, now: SystemTime) -> RetentionPlan {
+        snaps.sort_by(|a, b| {
+            b.timestamp
+                .cmp(&a.timestamp)
+                .then_with(|| b.name.cmp(&a.name))
+        });
+
+        let mut reasons: Vec<Vec<KeepReason>> = vec![Vec::new(); snaps.len()];
+        let rules: [Rule; 4] = [
+            (self.hourly, KeepReason::Hourly, |t| (t / 3600) as i64),
+            (self.daily, KeepReason::Daily, |t| days(t)),
+            (self.weekly, KeepReason::Weekly, |t| {
+                (days(t) + 3).div_euclid(7)
+            }),
+            (self.monthly, KeepReason::Monthly, |t| {
+                let (y, m) = civil_from_days(days(t));
+                y * 12 + i64::from(m)
+            }),
+        ];
+        for (count, reason, period) in rules.iter() {
+            let mut last = None;
+            let mut kept = 0;
+            for (i, s) in snaps.iter().enumerate() {
+                if kept == *count {
+                    break;
+                }
+                let p = period(s.timestamp);
+                if last != Some(p) {
+                    last = Some(p);
+                    kept += 1;
+                    reasons[i].push(*reason);
+                }
+            }
+        }
+
+        if let Some(within) = self.keep_within {
+            let now = secs(now);
+            for (i, s) in snaps.iter().enumerate() {
+                if s.timestamp.saturating_add(within.as_secs()) >= now {
+                    reasons[i].push(KeepReason::Within);
+                }
+            }
+        }
+
+        let mut plan = RetentionPlan::default();
+        for (snapshot, mut reasons) in snaps.into_iter().zip(reasons) {
+            if self.honour_holds && snapshot.held {
+                reasons.push(KeepReason::Held);
+            }
+            if reasons.is_empty() {
+                plan.destroy.push(snapshot);
+            } else {
+                plan.keep.push(Kept { snapshot, reasons });
+            }
+        }
+        plan
+    }
+
+    /// Decide which snapshots of `dataset` to keep, relative to the time `now`
+    ///
+    /// This only reads the snapshots (using read-only channel programs, so root privileges are
+    /// required): nothing is destroyed until the plan is executed. Large numbers of snapshots are
+    /// read in pages, as by [`Zfs::list()`].
+    pub fn plan(&self, zfs: &Zfs, dataset: &str, now: SystemTime) -> io::Result<RetentionPlan> {
+        let mut snaps = Vec::new();
+        let mut ignored = BTreeSet::new();
+        let mut after = None;
+        loop {
+            let page = list_page(
+                zfs,
+                dataset,
+                Contents::Snapshots,
+                after.as_deref(),
+                &["creation", "userrefs"],
+            )?;
+            for (name, props) in page.entries {
+                if let Some(s) = self.snapshot_info(name, &props, &mut ignored)? {
+                    snaps.push(s);
+                }
+            }
+            after = page.after;
+            if after.is_none() {
+                break;
+            }
+        }
+
+        let mut plan = self.select(snaps, now);
+        plan.ignored = ignored.into_iter().collect();
+        Ok(plan)
+    }
+
+    /// Decode a snapshot read by `plan()`, or add it to `ignored` if it isn't managed by this
+    /// policy
+    fn snapshot_info(
+        &self,
+        name: String,
+        props: &NvListRef,
+        ignored: &mut BTreeSet<String>,
+    ) -> io::Result<Option<SnapshotInfo>> {
+        let tag = name.find('@').map(|i| &name[i + 1..]).unwrap_or("");
+
+        if let Some(prefix) = &self.prefix {
+            if !tag.starts_with(prefix.as_str()) {
+                ignored.insert(name);
+                return Ok(None);
+            }
+        }
+        let timestamp = match &self.name_format {
+            Some(format) => match parse_time(format, tag) {
+                Some(t) => t,
+                None => {
+                    ignored.insert(name);
+                    return Ok(None);
+                }
+            },
+            None => number(props, "creation")?,
+        };
+        let held = number(props, "userrefs")? != 0;
+        Ok(Some(SnapshotInfo {
+            name,
+            timestamp,
+            held,
+        }))
+    }
+}
+
+impl RetentionPlan {
+    /// Destroy the snapshots in `destroy`, in batches
+    ///
+    /// Held snapshots are destroyed with [`Defer::Yes`]. Stops at the first batch which fails,
+    /// returning its error and the list of snapshots which failed.
+    pub fn execute(&self, zfs: &Zfs) -> Result<(), (io::Error, NvList)> {
+        for &(held, defer) in &[(false, Defer::No), (true, Defer::Yes)] {
+            let names: Vec<&str> = self
+                .destroy
+                .iter()
+                .filter(|s| s.held == held)
+                .map(|s| s.name.as_str())
+                .collect();
+            for batch in names.chunks(DESTROY_BATCH) {
+                zfs.destroy_snaps(batch.iter().cloned(), defer)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Lists the plan as `zfs destroy -nv` would, followed by the snapshots kept
+impl fmt::Display for RetentionPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for s in &self.destroy {
+            let deferred = if s.held { " (deferred)" } else { "" };
+            writeln!(f, "would destroy {}{}", s.name, deferred)?;
+        }
+        for k in &self.keep {
+            write!(f, "would keep {} (", k.snapshot.name)?;
+            for (i, r) in k.reasons.iter().enumerate() {
+                if i != 0 {
+                    f.write_str(", ")?;
+                }
+                write!(f, "{}", r)?;
+            }
+            writeln!(f, ")")?;
+        }
+        Ok(())
+    }
+}
+
+fn number(props: &NvListRef, name: &str) -> io::Result<u64> {
+    match props.lookup(name)?.data() {
+        NvData::Uint64(v) => Ok(v),
+        NvData::Int64(v) => Ok(v as u64),
+        d => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("unexpected value for {}: {:?}", name, d),
+        )),
+    }
+}
+
+fn secs(t: SystemTime) -> u64 {
+    t.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn days(t: u64) -> i64 {
+    (t / 86400) as i64
+}
+
+/// Days since 1970-01-01 of a date in the proleptic Gregorian calendar
+fn days_from_civil(y: i64, m: u32, d: u32) -> i64 {
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let doy = (153 * i64::from((m + 9) % 12) + 2) / 5 + i64::from(d) - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// The year and month of a number of days since 1970-01-01
+fn civil_from_days(z: i64) -> (i64, u32) {
+    let z = z + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    (yoe + era * 400 + if m <= 2 { 1 } else { 0 }, m)
+}
+
+/// Parse `s` with `format` (see [`RetentionPolicy::name_format`]) as a UTC time
+fn parse_time(format: &str, s: &str) -> Option<u64> {
+    let (mut y, mut mo, mut d, mut h, mut mi, mut sec) = (1970, 1, 1, 0, 0, 0);
+    let mut s = s.as_bytes();
+    let mut f = format.bytes();
+
+    fn digits(s: &mut &[u8], n: usize) -> Option<u32> {
+        if s.len() < n || !s[..n].iter().all(u8::is_ascii_digit) {
+            return None;
+        }
+        let v = s[..n].iter().fold(0, |v, c| v * 10 + u32::from(c - b'0'));
+        *s = &s[n..];
+        Some(v)
+    }
+
+    fn literal(s: &mut &[u8], c: u8) -> Option<()> {
+        match s.split_first() {
+            Some((&first, rest)) if first == c => {
+                *s = rest;
+                Some(())
+            }
+            _ => None,
+        }
+    }
+
+    while let Some(c) = f.next() {
+        if c == b'%' {
+            match f.next()? {
+                b'Y' => y = digits(&mut s, 4)?,
+                b'm' => mo = digits(&mut s, 2)?,
+                b'd' => d = digits(&mut s, 2)?,
+                b'H' => h = digits(&mut s, 2)?,
+                b'M' => mi = digits(&mut s, 2)?,
+                b'S' => sec = digits(&mut s, 2)?,
+                b'%' => literal(&mut s, b'%')?,
+                _ => return None,
+            }
+        } else {
+            literal(&mut s, c)?;
+        }
+    }
+
+    if !s.is_empty()
+        || !(1..=12).contains(&mo)
+        || !(1..=31).contains(&d)
+        || h > 23
+        || mi > 59
+        || sec > 60
+    {
+        return None;
+    }
+    let days = days_from_civil(i64::from(y), mo, d);
+    let t = days * 86400 + i64::from(h * 3600 + mi * 60 + sec);
+    if t < 0 {
+        None
+    } else {
+        Some(t as u64)
+    }
+}
@@ -0,0 +1,266 @@
+//! Helpers for creating snapshots
+use nvpair::{NvData, NvList, NvListRef};
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
+use std::{io, iter};
+
+use crate::props::check_user_prop;
+use crate::{Defer, Error, Zfs};
+
+/// Above this many datasets, [`Zfs::snapshot_recursive()`](crate::Zfs::snapshot_recursive) uses
+/// a channel program instead of a single large `lzc_snapshot()` argument list
+pub(crate) const MAX_LZC_SNAPSHOTS: usize = 1000;
+
+/// Snapshots `args.root` and all its descendants as `@args.tag`, setting the (user) properties in
+/// `args.props` on each snapshot.
+///
+/// Every snapshot is checked before any are created. If creating a snapshot or setting a property
+/// still fails, the snapshots already created are destroyed again (in the same transaction, so the
+/// partial set is never visible). Returns a list of snapshot name to error number for any which
+/// failed, and for any which couldn't be destroyed again; if it's empty, every snapshot was
+/// created.
+pub(crate) const SNAPSHOT_RECURSIVE_PROGRAM: &str = r#"
+args = ...
+local tag = args["tag"]
+local snaps = {}
+local function walk(ds)
+    snaps[#snaps + 1] = ds .. "@" .. tag
+    for child in zfs.list.children(ds) do
+        walk(child)
+    end
+end
+walk(args["root"])
+
+local errors = {}
+local failed = false
+for _, s in ipairs(snaps) do
+    local err = zfs.check.snapshot(s)
+    if err ~= 0 then
+        errors[s] = err
+        failed = true
+    end
+end
+if failed then
+    return errors
+end
+
+local created = {}
+for _, s in ipairs(snaps) do
+    local err = zfs.sync.snapshot(s)
+    if err ~= 0 then
+        errors[s] = err
+        failed = true
+        break
+    end
+    created[#created + 1] = s
+    for k, v in pairs(args["props"]) do
+        err = zfs.sync.set_prop(s, k, v)
+        if err ~= 0 then
+            errors[s] = err
+            failed = true
+        end
+    end
+    if failed then
+        break
+    end
+end
+if failed then
+    for _, s in ipairs(created) do
+        local err = zfs.sync.destroy(s)
+        if err ~= 0 then
+            errors[s] = err
+        end
+    end
+end
+return errors
+"#;
+
+/// User properties to set on snapshots as they are created
+///
+/// Setters may be chained. The first invalid property is reported when the snapshots are
+/// created.
+///
+/// ```no_run
+/// use zfs_core::SnapshotProps;
+/// let props = SnapshotProps::new()
+///     .user("com.example:retention", "30d")
+///     .user("com.example:created-by", "backup");
+/// # let zfs = zfs_core::Zfs::new().unwrap();
+/// zfs.snapshot_with_props(vec!["tank/data@daily-1"], props)?;
+/// # Ok::<(), zfs_core::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct SnapshotProps {
+    nv: NvList,
+    error: Option<io::Error>,
+}
+
+impl Default for SnapshotProps {
+    fn default() -> Self {
+        SnapshotProps::new()
+    }
+}
+
+impl SnapshotProps {
+    pub fn new() -> Self {
+        SnapshotProps {
+            nv: NvList::new_unique_names(),
+            error: None,
+        }
+    }
+
+    /// A user property. `name` must contain a `:`, as in `com.example:retention`, and may only
+    /// contain lowercase letters, digits and `-`, `_`, `.` or `:`.
+    pub fn user(mut self, name: &str, value: &str) -> Self {
+        if self.error.is_some() {
+            return self;
+        }
+        let r = match check_user_prop(name, value) {
+            Ok(()) => self.nv.insert(name, value).map_err(io::Error::from),
+            Err(msg) => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{}: {}", name, msg),
+            )),
+        };
+        if let Err(e) = r {
+            self.error = Some(e);
+        }
+        self
+    }
+
+    /// Return the nvlist to pass to `lzc_snapshot()`
+    pub fn build(self) -> io::Result<NvList> {
+        match self.error {
+            Some(e) => Err(e),
+            None => Ok(self.nv),
+        }
+    }
+}
+
+/// Split a snapshot name into its dataset and tag
+pub(crate) fn split_snapshot(name: &str) -> io::Result<(&str, &str)> {
+    let mut parts = name.splitn(2, '@');
+    match (parts.next(), parts.next()) {
+        (Some(ds), Some(tag)) if !ds.is_empty() && !tag.is_empty() && !tag.contains('@') => {
+            Ok((ds, tag))
+        }
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{:?} is not a snapshot name", name),
+        )),
+    }
+}
+
+/// Convert the errors returned by `SNAPSHOT_RECURSIVE_PROGRAM` (which lua returns as `int64`) into
+/// the `int32` form used by `lzc_*` error lists, or `None` if there were no errors
+pub(crate) fn program_errors(out: &NvListRef) -> io::Result<Option<NvList>> {
+    let ret = out.lookup_nvlist("return")?;
+    let mut errors = NvList::try_new_unique_names()?;
+    for pair in ret {
+        let errno = match pair.data() {
+            NvData::Int64(v) => v as i32,
+            NvData::Int32(v) => v,
+            d => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("unexpected error for {:?}: {:?}", pair.name(), d),
+                ))
+            }
+        };
+        errors.insert(pair.name(), &errno)?;
+    }
+    Ok(if errors.is_empty() {
+        None
+    } else {
+        Some(errors)
+    })
+}
+
+/// How many random names [`Zfs::temp_snapshot()`] tries before giving up
+const TEMP_SNAPSHOT_RETRIES: u32 = 100;
+/// How many random characters are in the name of a temporary snapshot
+const TEMP_SNAPSHOT_RAND_CHARS: usize = 12;
+
+/// A snapshot which is destroyed when dropped, created by [`Zfs::temp_snapshot()`]
+///
+/// If the snapshot is held when dropped, it is destroyed with [`Defer::Yes`], so it disappears
+/// once the holds are released. Errors while destroying it on drop are ignored: use
+/// [`destroy()`](Self::destroy) to observe them.
+#[derive(Debug)]
+pub struct TempSnapshot<'a> {
+    zfs: &'a Zfs,
+    name: String,
+    keep: bool,
+}
+
+impl<'a> TempSnapshot<'a> {
+    /// The full name of the snapshot, `fs@tmp-<random>`
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Keep the snapshot instead of destroying it, returning its name
+    pub fn keep(mut self) -> String {
+        self.keep = true;
+        std::mem::take(&mut self.name)
+    }
+
+    /// Destroy the snapshot now, reporting any errors
+    pub fn destroy(mut self) -> Result<(), (io::Error, NvList)> {
+        self.keep = true;
+        self.zfs
+            .destroy_snaps(iter::once(self.name.as_str()), Defer::Yes)
+    }
+}
+
+impl<'a> Drop for TempSnapshot<'a> {
+    fn drop(&mut self) {
+        if !self.keep {
+            let _ = self
+                .zfs
+                .destroy_snaps(iter::once(self.name.as_str()), Defer::Yes);
+        }
+    }
+}
+
+/// Was snapshot creation refused only because the name is in use?
+fn already_exists(e: &Error) -> bool {
+    match e {
+        Error::Io { source } => source.kind() == io::ErrorKind::AlreadyExists,
+        Error::List { source } => {
+            let mut errors = source.iter().peekable();
+            errors.peek().is_some() && errors.all(|(_, e)| e.kind() == io::ErrorKind::AlreadyExists)
+        }
+    }
+}
+
+pub(crate) fn temp_snapshot<'a>(zfs: &'a Zfs, fs: &str) -> Result<TempSnapshot<'a>, Error> {
+    let mut rng = thread_rng();
+    for _ in 0..TEMP_SNAPSHOT_RETRIES {
+        let suffix: String = (&mut rng)
+            .sample_iter(Alphanumeric)
+            .take(TEMP_SNAPSHOT_RAND_CHARS)
+            .map(|x| x as char)
+            .collect();
+        let name = format!("{}@tmp-{}", fs, suffix);
+
+        match zfs.snapshot(iter::once(name.as_str())) {
+            Ok(()) => {
+                return Ok(TempSnapshot {
+                    zfs,
+                    name,
+                    keep: false,
+                })
+            }
+            Err(ref e) if already_exists(e) => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    Err(Error::Io {
+        source: io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            "too many temporary snapshots already exist",
+        ),
+    })
+}
@@ -148,6 +148,306 @@ fn create_destroy() {
     z.destroy(&b).unwrap();
 }
 
+#[test]
+fn create_encrypted() {
+    let tmpfs = TempFs::new("create_encrypted").unwrap();
+    let z = zfs::Zfs::new().unwrap();
+    let nv = nvpair::NvList::new();
+
+    let mut b = tmpfs.path().to_owned();
+    b.push_str("/passphrase");
+    let params = zfs::EncryptionParams::new(zfs::KeyFormat::Passphrase, &b"hunter2hunter2"[..])
+        .pbkdf2_iters(100_000);
+    z.create_encrypted(&b, zfs::DataSetType::Zfs, &nv, &params)
+        .unwrap_or_else(|e| panic!("create {:?} failed: {}", b, e));
+    assert!(z.exists(&b));
+    z.destroy(&b).unwrap();
+
+    let mut b = tmpfs.path().to_owned();
+    b.push_str("/hex");
+    let params = zfs::EncryptionParams::new(zfs::KeyFormat::Hex, &[b'a'; 64][..])
+        .location(zfs::KeyLocation::Uri("file:///nonexistent".to_owned()));
+    z.create_encrypted(&b, zfs::DataSetType::Zfs, &nv, &params)
+        .unwrap_or_else(|e| panic!("create {:?} failed: {}", b, e));
+    z.destroy(&b).unwrap();
+
+    // too short to be a raw key
+    let params = zfs::EncryptionParams::new(zfs::KeyFormat::Raw, &[0u8; 16][..]);
+    let e = z
+        .create_encrypted(&b, zfs::DataSetType::Zfs, &nv, &params)
+        .unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+    assert!(!z.exists(&b));
+}
+
+#[test]
+fn key_manager() {
+    if !have_root_privs() {
+        eprintln!("skipping key_manager, need root privs");
+        return;
+    }
+
+    let tmpfs = TempFs::new("key_manager").unwrap();
+    let z = zfs::Zfs::new().unwrap();
+    let keys = z.key_manager();
+    let nv = nvpair::NvList::new();
+
+    assert_eq!(keys.key_status(tmpfs.path()).unwrap(), zfs::KeyStatus::None);
+
+    let b = tmpfs.path().to_owned() + "/enc";
+    let params = zfs::EncryptionParams::new(zfs::KeyFormat::Passphrase, "hunter2hunter2")
+        .pbkdf2_iters(100_000);
+    z.create_encrypted(&b, zfs::DataSetType::Zfs, &nv, &params)
+        .unwrap();
+
+    let props = keys.key_props(&b).unwrap();
+    assert_eq!(props.status, zfs::KeyStatus::Available);
+    assert_eq!(props.format, Some(zfs::KeyFormat::Passphrase));
+    assert_eq!(props.pbkdf2_iters, 100_000);
+
+    assert!(keys.check(&b, "hunter2hunter2").unwrap());
+    assert!(!keys.check(&b, "wrong password").unwrap());
+
+    keys.unload(&b).unwrap();
+    assert_eq!(keys.key_status(&b).unwrap(), zfs::KeyStatus::Unavailable);
+    keys.load(&b, "hunter2hunter2").unwrap();
+    assert_eq!(keys.key_status(&b).unwrap(), zfs::KeyStatus::Available);
+
+    let new_key = zfs::EncryptionParams::new(zfs::KeyFormat::Hex, &[b'f'; 64][..]);
+    keys.change(&b, zfs::CryptCmd::NewKey, Some(&new_key))
+        .unwrap();
+    keys.unload(&b).unwrap();
+    keys.load(&b, &[b'f'; 64][..]).unwrap();
+
+    let e = keys.change(&b, zfs::CryptCmd::NewKey, None).unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+
+    z.destroy(&b).unwrap();
+}
+
+#[test]
+fn wrapping_key_derive() {
+    let hex = zfs::WrappingKey::derive(zfs::KeyFormat::Hex, &[b'0'; 64], 0, 0).unwrap();
+    assert_eq!(hex.as_bytes(), &[0u8; 32][..]);
+
+    let hex = b"000102030405060708090a0b0c0d0e0f101112131415161718191A1B1C1D1E1F";
+    let key = zfs::WrappingKey::derive(zfs::KeyFormat::Hex, hex, 0, 0).unwrap();
+    assert_eq!(key.as_bytes(), &(0..32).collect::<Vec<u8>>()[..]);
+
+    // the same passphrase with a different salt gives a different key
+    let a = zfs::WrappingKey::derive(zfs::KeyFormat::Passphrase, b"password", 1, 100_000).unwrap();
+    let b = zfs::WrappingKey::derive(zfs::KeyFormat::Passphrase, b"password", 2, 100_000).unwrap();
+    assert_ne!(a.as_bytes(), b.as_bytes());
+
+    zfs::WrappingKey::derive(zfs::KeyFormat::Passphrase, b"short", 1, 100_000).unwrap_err();
+    zfs::WrappingKey::derive(zfs::KeyFormat::Passphrase, b"password", 1, 10).unwrap_err();
+    zfs::WrappingKey::derive(zfs::KeyFormat::Hex, &[b'g'; 64], 0, 0).unwrap_err();
+}
+
+#[test]
+fn dataset_props() {
+    let nv = zfs::DatasetProps::new(zfs::DataSetType::Zfs)
+        .compression(zfs::Compression::Zstd(3))
+        .recordsize(1024 * 1024)
+        .atime(false)
+        .canmount(zfs::CanMount::NoAuto)
+        .mountpoint("legacy")
+        .user("com.example:owner", "alice")
+        .build()
+        .unwrap();
+    assert_eq!(nv.lookup_uint64("compression").unwrap(), 16 | 3 << 7);
+    assert_eq!(nv.lookup_uint64("recordsize").unwrap(), 1024 * 1024);
+    assert_eq!(nv.lookup_uint64("atime").unwrap(), 0);
+    assert_eq!(nv.lookup_uint64("canmount").unwrap(), 2);
+    assert_eq!(
+        nv.lookup_string("mountpoint").unwrap().to_str().unwrap(),
+        "legacy"
+    );
+    assert_eq!(
+        nv.lookup_string("com.example:owner")
+            .unwrap()
+            .to_str()
+            .unwrap(),
+        "alice"
+    );
+
+    let bad = |p: zfs::DatasetProps| p.build().unwrap_err().kind();
+    let fs = || zfs::DatasetProps::new(zfs::DataSetType::Zfs);
+    let vol = || zfs::DatasetProps::new(zfs::DataSetType::Zvol);
+    assert_eq!(bad(fs().recordsize(3000)), io::ErrorKind::InvalidInput);
+    assert_eq!(
+        bad(fs().compression(zfs::Compression::Gzip(10))),
+        io::ErrorKind::InvalidInput
+    );
+    assert_eq!(bad(fs().copies(4)), io::ErrorKind::InvalidInput);
+    assert_eq!(
+        bad(fs().mountpoint("relative")),
+        io::ErrorKind::InvalidInput
+    );
+    assert_eq!(bad(fs().volsize(1 << 30)), io::ErrorKind::InvalidInput);
+    assert_eq!(bad(fs().user("nocolon", "x")), io::ErrorKind::InvalidInput);
+    assert_eq!(bad(vol().recordsize(4096)), io::ErrorKind::InvalidInput);
+    assert_eq!(bad(vol()), io::ErrorKind::InvalidInput);
+    assert_eq!(
+        bad(vol().volblocksize(8192).volsize(12288)),
+        io::ErrorKind::InvalidInput
+    );
+
+    let nv = vol()
+        .volsize(1 << 30)
+        .volblocksize(16384)
+        .volmode(zfs::VolMode::Dev)
+        .compression(zfs::Compression::ZstdFast(20))
+        .build()
+        .unwrap();
+    assert_eq!(nv.lookup_uint64("volmode").unwrap(), 2);
+    assert_eq!(nv.lookup_uint64("compression").unwrap(), 16 | 113 << 7);
+}
+
+#[test]
+fn create_with_props() {
+    let tmpfs = TempFs::new("create_with_props").unwrap();
+    let z = zfs::Zfs::new().unwrap();
+
+    let b = tmpfs.path().to_owned() + "/props";
+    let props = zfs::DatasetProps::new(zfs::DataSetType::Zfs)
+        .compression(zfs::Compression::Lz4)
+        .atime(false)
+        .canmount(zfs::CanMount::Off);
+    z.create_with_props(&b, props)
+        .unwrap_or_else(|e| panic!("create {:?} failed: {}", b, e));
+    assert!(z.exists(&b));
+    z.destroy(&b).unwrap();
+}
+
+#[test]
+fn get_props() {
+    if !have_root_privs() {
+        eprintln!("skipping get_props, need root privs");
+        return;
+    }
+
+    let tmpfs = TempFs::new("get_props").unwrap();
+    let z = zfs::Zfs::new().unwrap();
+
+    let b = tmpfs.path().to_owned() + "/props";
+    let props = zfs::DatasetProps::new(zfs::DataSetType::Zfs)
+        .compression(zfs::Compression::Lz4)
+        .user("org.example:owner", "someone");
+    z.create_with_props(&b, props).unwrap();
+
+    let r = z
+        .get_props(
+            &b,
+            &[
+                zfs::Prop::Used,
+                zfs::DatasetProp::Compression.into(),
+                zfs::Prop::Type,
+                zfs::Prop::User("org.example:owner".to_owned()),
+                zfs::Prop::User("org.example:unset".to_owned()),
+            ],
+        )
+        .unwrap();
+    assert_eq!(r.len(), 5);
+    assert_eq!(r[0].prop, zfs::Prop::Used);
+    assert!(r[0].value.as_ref().unwrap().as_u64().is_some());
+    assert_eq!(r[1].value.as_ref().unwrap().as_str(), Some("lz4"));
+    assert_eq!(r[1].source, zfs::PropSource::Local);
+    assert_eq!(r[2].value.as_ref().unwrap().as_str(), Some("filesystem"));
+    assert_eq!(r[3].value.as_ref().unwrap().as_str(), Some("someone"));
+    assert_eq!(r[3].source, zfs::PropSource::Local);
+    assert_eq!(r[4].value, None);
+
+    z.destroy(&b).unwrap();
+}
+
+#[test]
+fn list() {
+    if !have_root_privs() {
+        eprintln!("skipping list, need root privs");
+        return;
+    }
+
+    let tmpfs = TempFs::new("list").unwrap();
+    let z = zfs::Zfs::new().unwrap();
+    let nv = nvpair::NvList::new();
+
+    let root = tmpfs.path().to_owned();
+    let a = root.clone() + "/a";
+    let b = a.clone() + "/b";
+    let snap = a.clone() + "@s";
+    z.create(&a, zfs::DataSetType::Zfs, &nv).unwrap();
+    z.create(&b, zfs::DataSetType::Zfs, &nv).unwrap();
+    z.snapshot(vec![snap.clone()]).unwrap();
+
+    let names = |opts| -> Vec<String> {
+        z.list(root.clone(), opts)
+            .map(|e| e.unwrap().name)
+            .collect()
+    };
+
+    assert_eq!(names(zfs::ListOptions::default()), vec![root.clone()]);
+    assert_eq!(
+        names(zfs::ListOptions {
+            recursive: true,
+            ..Default::default()
+        }),
+        vec![root.clone(), a.clone(), b.clone()]
+    );
+    assert_eq!(
+        names(zfs::ListOptions {
+            depth: Some(1),
+            ..Default::default()
+        }),
+        vec![root.clone(), a.clone()]
+    );
+    assert_eq!(
+        names(zfs::ListOptions {
+            recursive: true,
+            types: vec![zfs::ListType::Snapshot],
+            ..Default::default()
+        }),
+        vec![snap.clone()]
+    );
+
+    z.destroy(&snap).unwrap();
+    z.destroy(&b).unwrap();
+    z.destroy(&a).unwrap();
+}
+
+#[test]
+fn snapshot_recursive() {
+    if !have_root_privs() {
+        eprintln!("skipping snapshot_recursive, need root privs");
+        return;
+    }
+
+    let tmpfs = TempFs::new("snapshot_recursive").unwrap();
+    let z = zfs::Zfs::new().unwrap();
+    let nv = nvpair::NvList::new();
+
+    let a = tmpfs.path().to_owned() + "/a";
+    let b = a.clone() + "/b";
+    z.create(&a, zfs::DataSetType::Zfs, &nv).unwrap();
+    z.create(&b, zfs::DataSetType::Zfs, &nv).unwrap();
+
+    z.snapshot_recursive(a.clone() + "@r", zfs::SnapshotProps::new())
+        .unwrap();
+    assert!(z.exists(a.clone() + "@r"));
+    assert!(z.exists(b.clone() + "@r"));
+
+    // the existing snapshots make every new one fail
+    match z.snapshot_recursive(a.clone() + "@r", zfs::SnapshotProps::new()) {
+        Err(zfs::Error::List { source }) => assert_eq!(source.iter().count(), 2),
+        r => panic!("unexpected result: {:?}", r),
+    }
+
+    z.destroy_snaps(vec![a.clone() + "@r", b.clone() + "@r"], zfs::Defer::No)
+        .unwrap();
+    z.destroy(&b).unwrap();
+    z.destroy(&a).unwrap();
+}
+
 #[test]
 fn rename() {
     let tmpfs = TempFs::new("rename").unwrap();
@@ -201,6 +501,161 @@ fn snapshot() {
     z.destroy(&b).unwrap();
 }
 
+#[test]
+fn snapshot_with_props() {
+    if !have_root_privs() {
+        eprintln!("skipping snapshot_with_props, need root privs");
+        return;
+    }
+
+    let tmpfs = TempFs::new("snapshot_with_props").unwrap();
+    let z = zfs::Zfs::new().unwrap();
+
+    let snap = tmpfs.path().to_owned() + "@tagged";
+    let props = zfs::SnapshotProps::new().user("com.example:retention", "30d");
+    z.snapshot_with_props(vec![snap.clone()], props).unwrap();
+
+    let r = z
+        .get_props(
+            &snap,
+            &[zfs::Prop::User("com.example:retention".to_owned())],
+        )
+        .unwrap();
+    assert_eq!(r[0].value.as_ref().unwrap().as_str(), Some("30d"));
+
+    for name in &[
+        "noseparator",
+        "com.example:bad name",
+        "com.example:bad/name",
+        "com.Example:Owner",
+    ] {
+        let props = zfs::SnapshotProps::new().user(name, "x");
+        assert!(props.build().is_err(), "{:?} accepted", name);
+    }
+
+    z.destroy(&snap).unwrap();
+}
+
+#[test]
+fn retention_select() {
+    use std::time::{Duration, UNIX_EPOCH};
+    use zfs::{KeepReason, RetentionPolicy, SnapshotInfo};
+
+    // hourly snapshots from 2021-03-01T00:00:00Z to 2021-03-03T23:00:00Z
+    let start = 1614556800;
+    let snaps: Vec<_> = (0..72)
+        .map(|i| SnapshotInfo {
+            name: format!("tank/fs@h{}", i),
+            timestamp: start + i * 3600,
+            held: i == 0,
+        })
+        .collect();
+    let now = UNIX_EPOCH + Duration::from_secs(start + 72 * 3600);
+
+    let policy = RetentionPolicy {
+        hourly: 5,
+        daily: 2,
+        honour_holds: true,
+        ..Default::default()
+    };
+    let plan = policy.select(snaps.clone(), now);
+    let kept: Vec<_> = plan
+        .keep
+        .iter()
+        .map(|k| (k.snapshot.name.as_str(), k.reasons.clone()))
+        .collect();
+    assert_eq!(
+        kept,
+        vec![
+            ("tank/fs@h71", vec![KeepReason::Hourly, KeepReason::Daily]),
+            ("tank/fs@h70", vec![KeepReason::Hourly]),
+            ("tank/fs@h69", vec![KeepReason::Hourly]),
+            ("tank/fs@h68", vec![KeepReason::Hourly]),
+            ("tank/fs@h67", vec![KeepReason::Hourly]),
+            ("tank/fs@h47", vec![KeepReason::Daily]),
+            ("tank/fs@h0", vec![KeepReason::Held]),
+        ]
+    );
+    assert_eq!(plan.destroy.len(), 65);
+    assert_eq!(plan.destroy[0].name, "tank/fs@h66");
+
+    let policy = RetentionPolicy {
+        keep_within: Some(Duration::from_secs(3 * 3600)),
+        ..Default::default()
+    };
+    let plan = policy.select(snaps, now);
+    assert_eq!(plan.keep.len(), 3);
+    assert_eq!(plan.destroy.len(), 69);
+    assert!(plan.destroy.iter().any(|s| s.held));
+}
+
+#[test]
+fn retention_plan() {
+    if !have_root_privs() {
+        eprintln!("skipping retention_plan, need root privs");
+        return;
+    }
+
+    use std::time::SystemTime;
+
+    let tmpfs = TempFs::new("retention_plan").unwrap();
+    let z = zfs::Zfs::new().unwrap();
+
+    let fs = tmpfs.path().to_owned();
+    let snaps: Vec<String> = ["auto-2021-03-01_0000", "auto-2021-03-02_0000", "manual"]
+        .iter()
+        .map(|t| format!("{}@{}", fs, t))
+        .collect();
+    for s in &snaps {
+        z.snapshot(vec![s.clone()]).unwrap();
+    }
+
+    let policy = zfs::RetentionPolicy {
+        daily: 1,
+        name_format: Some("auto-%Y-%m-%d_%H%M".to_owned()),
+        ..Default::default()
+    };
+    let plan = policy.plan(&z, &fs, SystemTime::now()).unwrap();
+    assert_eq!(plan.keep.len(), 1);
+    assert_eq!(plan.keep[0].snapshot.name, snaps[1]);
+    assert_eq!(plan.keep[0].snapshot.timestamp, 1614643200);
+    assert_eq!(plan.destroy.len(), 1);
+    assert_eq!(plan.destroy[0].name, snaps[0]);
+    assert_eq!(plan.ignored, vec![snaps[2].clone()]);
+
+    plan.execute(&z).unwrap();
+    assert!(!z.exists(&snaps[0]));
+    assert!(z.exists(&snaps[1]));
+
+    z.destroy_snaps(vec![snaps[1].clone(), snaps[2].clone()], zfs::Defer::No)
+        .unwrap();
+}
+
+#[test]
+fn temp_snapshot() {
+    let tmpfs = TempFs::new("temp_snapshot").unwrap();
+    let z = zfs::Zfs::new().unwrap();
+
+    let snap = z.temp_snapshot(tmpfs.path()).unwrap();
+    let name = snap.name().to_owned();
+    assert!(name.starts_with(&(tmpfs.path().to_owned() + "@tmp-")));
+    assert!(z.exists(&name));
+    drop(snap);
+    assert!(!z.exists(&name));
+
+    // dropped while unwinding
+    let r = std::panic::catch_unwind(|| {
+        let snap = z.temp_snapshot(tmpfs.path()).unwrap();
+        panic!("{}", snap.name().to_owned());
+    });
+    let name = r.unwrap_err().downcast::<String>().unwrap();
+    assert!(!z.exists(&*name));
+
+    let name = z.temp_snapshot(tmpfs.path()).unwrap().keep();
+    assert!(z.exists(&name));
+    z.destroy_snaps(vec![name], zfs::Defer::No).unwrap();
+}
+
 #[test]
 fn snapshot_multi() {
     let tmpfs = TempFs::new("snapshot_multi").unwrap();
@@ -480,6 +935,34 @@ fn get_holds() {
     .unwrap();
 }
 
+#[test]
+fn hold_temporary() {
+    let tmpfs = TempFs::new("hold_temporary").unwrap();
+    let z = zfs::Zfs::new().unwrap();
+
+    let snap = tmpfs.path().to_owned() + "@snap";
+    z.snapshot(vec![snap.clone()]).unwrap();
+
+    let guard = z
+        .hold_temporary(&[(snap.clone(), "test-hold-temp")])
+        .unwrap();
+    assert_eq!(guard.holds().count(), 1);
+    assert_eq!(z.get_holds(&snap).unwrap().as_ref().iter().count(), 1);
+    // held snapshots can't be destroyed
+    assert!(z.destroy_snaps(vec![snap.clone()], zfs::Defer::No).is_err());
+
+    drop(guard);
+    assert_eq!(z.get_holds(&snap).unwrap().as_ref().iter().count(), 0);
+
+    let guard = z
+        .hold_temporary(&[(snap.clone(), "test-hold-temp")])
+        .unwrap();
+    guard.release().unwrap();
+    assert_eq!(z.get_holds(&snap).unwrap().as_ref().iter().count(), 0);
+
+    z.destroy_snaps(vec![snap], zfs::Defer::No).unwrap();
+}
+
 #[test]
 fn send_recv() {
     let tmpfs = TempFs::new("send_recv").unwrap();